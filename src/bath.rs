@@ -2,7 +2,7 @@
 
 use std::path::{Component, Path, PathBuf};
 
-use crate::bog::BogUnwrapExt;
+use crate::{bog::BogUnwrapExt, dbog, ebog, wbog};
 
 /// Get the (lossy) basename of a valid path
 /// Exits if path terminates in ..
@@ -11,8 +11,16 @@ pub fn basename(path: &Path) -> Cow<'_, str> {
     path.file_name().or_err(&err_prefix).to_string_lossy()
 }
 
-/// Split path around last '.'
+/// Split a file name around its last '.', into stem and extension (like [`Path::file_stem`] and [`Path::extension`]):
+/// - No dot: `archive` -> `["archive", ""]`
+/// - A leading dot is part of the stem, so dotfiles have no extension: `.gitignore` -> `[".gitignore", ""]`,
+///   but only the last dot splits: `.tar.gz` -> `[".tar", "gz"]`
+/// - A trailing dot is dropped with an empty extension: `file.` -> `["file", ""]`
+/// - `.` and `..` have no extension
 pub fn split_ext(p: &str) -> [&str; 2] {
+    if p == ".." {
+        return [p, ""];
+    }
     match p.rfind('.') {
         Some(0) | None => [p, ""],
         Some(idx) if idx + 1 < p.len() => [&p[..idx], &p[idx + 1..]],
@@ -20,10 +28,280 @@ pub fn split_ext(p: &str) -> [&str; 2] {
     }
 }
 
+/// Make a string safe to use as a filename on all platforms
+/// - Reserved characters (`<>:"/\|?*`) and control characters are replaced with '_'
+/// - Trailing dots and spaces are trimmed
+/// - Reserved device names (CON, NUL, COM1, ...) have '_' appended to the stem
+///
+/// Never returns an empty string.
+pub fn sanitize_filename(name: &str) -> String {
+    let mut ret: String = name
+        .chars()
+        .map(|c| {
+            if c.is_control() || matches!(c, '<' | '>' | ':' | '"' | '/' | '\\' | '|' | '?' | '*') {
+                '_'
+            } else {
+                c
+            }
+        })
+        .collect();
+
+    ret.truncate(ret.trim_end_matches(['.', ' ']).len());
+    if ret.is_empty() {
+        return "_".to_string();
+    }
+
+    // CON.txt is as reserved as CON
+    let stem = ret.split('.').next().unwrap_or_default().trim_end();
+    if is_reserved_filename(stem) {
+        ret.insert(stem.len(), '_');
+    }
+    ret
+}
+
+fn is_reserved_filename(stem: &str) -> bool {
+    let b = stem.as_bytes();
+    ["CON", "PRN", "AUX", "NUL"]
+        .iter()
+        .any(|r| stem.eq_ignore_ascii_case(r))
+        || (b.len() == 4
+            && (b[..3].eq_ignore_ascii_case(b"COM") || b[..3].eq_ignore_ascii_case(b"LPT"))
+            && matches!(b[3], b'1'..=b'9'))
+}
+
+/// Get a path that isn't taken by appending ` (n)` to the file stem: `file.txt` -> `file (1).txt`.
+/// Returns the path unchanged if nothing exists there yet.
+pub fn unique_path(path: impl AsRef<Path>) -> PathBuf {
+    const MAX_ATTEMPTS: usize = 10000;
+    let path = path.as_ref();
+    // don't clobber broken symlinks either
+    let taken = |p: &Path| p.symlink_metadata().is_ok();
+
+    if !taken(path) {
+        return path.to_path_buf();
+    }
+    let Some(name) = path.file_name() else {
+        return path.to_path_buf();
+    };
+    let name = name.to_string_lossy();
+    let [stem, ext] = split_ext(&name);
+
+    let mut candidate = path.to_path_buf();
+    for i in 1..=MAX_ATTEMPTS {
+        candidate = if ext.is_empty() {
+            path.with_file_name(format!("{stem} ({i})"))
+        } else {
+            path.with_file_name(format!("{stem} ({i}).{ext}"))
+        };
+        if !taken(&candidate) {
+            return candidate;
+        }
+    }
+
+    wbog!("No free name found for {path:?} after {MAX_ATTEMPTS} attempts");
+    candidate
+}
+
+/// Get the longest shared prefix of the normalized paths.
+/// A single path (or identical paths) returns the path itself.
+/// Returns None if paths is empty or they share no components (i.e. different drives).
+pub fn common_ancestor(paths: &[impl AsRef<Path>]) -> Option<PathBuf> {
+    let (first, rest) = paths.split_first()?;
+    let first = first.as_ref().normalize();
+    let mut common: Vec<Component> = first.components().collect();
+
+    for path in rest {
+        let path = path.as_ref().normalize();
+        let shared = common
+            .iter()
+            .zip(path.components())
+            .take_while(|(a, b)| **a == *b)
+            .count();
+        common.truncate(shared);
+    }
+
+    if common.is_empty() {
+        None
+    } else {
+        Some(common.iter().collect())
+    }
+}
+
+/// Find the closest directory (starting at start itself) containing an entry named needle, e.g. `.git` or `Cargo.toml`.
+/// Relative starts are resolved against the current directory. The search stops at the root.
+pub fn find_upwards(start: impl AsRef<Path>, needle: &str) -> Option<PathBuf> {
+    find_upwards_any(start, &[needle])
+}
+
+/// [`find_upwards`], stopping at the first directory containing any of needles
+pub fn find_upwards_any(start: impl AsRef<Path>, needles: &[&str]) -> Option<PathBuf> {
+    let start = start.as_ref().abs_cwd();
+
+    start
+        .ancestors()
+        .find(|dir| {
+            // symlink_metadata, so that dangling symlinks also count
+            needles
+                .iter()
+                .any(|needle| dir.join(needle).symlink_metadata().is_ok())
+        })
+        .map(Path::to_path_buf)
+}
+
+/// Canonicalize the longest existing ancestor of path, then re-append the rest and normalize.
+/// Unlike [`std::fs::canonicalize`], this works for paths which don't exist yet.
+/// Falls back to [`PathExt::abs`] against the current directory if no ancestor can be canonicalized.
+pub fn canonicalize_lenient(path: impl AsRef<Path>) -> PathBuf {
+    let path = path.as_ref();
+
+    for ancestor in path.ancestors() {
+        let canonical = if ancestor.as_os_str().is_empty() {
+            Path::new(".").canonicalize()
+        } else {
+            ancestor.canonicalize()
+        };
+
+        if let Ok(canonical) = canonical {
+            // ancestors are always prefixes of path
+            let tail = path.strip_prefix(ancestor).unwrap_or(path);
+            return canonical.join(tail).normalize();
+        }
+    }
+
+    dbog!("Failed to canonicalize any ancestor of {path:?}");
+    path.abs(std::env::current_dir().unwrap_or_default())
+}
+
+/// Match a path string against a shell-style glob pattern.
+/// - `?` matches any single character except '/'
+/// - `*` matches any sequence of characters except '/'
+/// - `**` matches any sequence of characters including '/', and `**/` also matches no directories
+/// - `[abc]`, `[a-z]`, `[!abc]` match a character class (never '/')
+///
+/// Like the shell, wildcards don't match a '.' at the start of a component: use `.*` to match dotfiles.
+/// Brace expansion is not supported.
+pub fn glob_match(pattern: &str, path: &str) -> bool {
+    let p: Vec<char> = pattern.chars().collect();
+    let s: Vec<char> = path.chars().collect();
+    glob_match_at(&p, &s, 0)
+}
+
+fn glob_match_at(p: &[char], s: &[char], i: usize) -> bool {
+    let is_hidden_start = |j: usize| s[j] == '.' && (j == 0 || s[j - 1] == '/');
+
+    match p.first() {
+        None => i == s.len(),
+        Some('*') => {
+            let cross = p.get(1) == Some(&'*');
+            let rest = if cross { &p[2..] } else { &p[1..] };
+            if cross && rest.first() == Some(&'/') && glob_match_at(&rest[1..], s, i) {
+                return true;
+            }
+
+            let mut j = i;
+            loop {
+                if glob_match_at(rest, s, j) {
+                    return true;
+                }
+                if j == s.len() || (s[j] == '/' && !cross) || is_hidden_start(j) {
+                    return false;
+                }
+                j += 1;
+            }
+        }
+        Some('?') => {
+            i < s.len() && s[i] != '/' && !is_hidden_start(i) && glob_match_at(&p[1..], s, i + 1)
+        }
+        Some('[') => {
+            // the first member may be ']'
+            let start = if matches!(p.get(1), Some('!' | '^')) { 2 } else { 1 };
+            let close = p
+                .get(start + 1..)
+                .and_then(|rest| rest.iter().position(|&c| c == ']'))
+                .map(|pos| start + 1 + pos);
+
+            match close {
+                Some(close) => {
+                    i < s.len()
+                        && s[i] != '/'
+                        && glob_class_contains(&p[1..close], s[i])
+                        && glob_match_at(&p[close + 1..], s, i + 1)
+                }
+                // unterminated class is a literal '['
+                None => i < s.len() && s[i] == '[' && glob_match_at(&p[1..], s, i + 1),
+            }
+        }
+        Some(&c) => i < s.len() && s[i] == c && glob_match_at(&p[1..], s, i + 1),
+    }
+}
+
+fn glob_class_contains(class: &[char], c: char) -> bool {
+    let (negate, class) = match class.first() {
+        Some('!' | '^') => (true, &class[1..]),
+        _ => (false, class),
+    };
+
+    let mut found = false;
+    let mut k = 0;
+    while k < class.len() {
+        if k + 2 < class.len() && class[k + 1] == '-' {
+            found |= class[k] <= c && c <= class[k + 2];
+            k += 3;
+        } else {
+            found |= class[k] == c;
+            k += 1;
+        }
+    }
+    found != negate
+}
+
+/// Compare the normalized paths component-wise, ignoring (unicode) case.
+/// This is a heuristic for case-insensitive filesystems: the actual filesystem is not queried.
+/// Non-UTF-8 components must match exactly.
+pub fn paths_equal_ignore_case(a: impl AsRef<Path>, b: impl AsRef<Path>) -> bool {
+    let a = a.as_ref().normalize();
+    let b = b.as_ref().normalize();
+    let (mut a, mut b) = (a.components(), b.components());
+
+    loop {
+        match (a.next(), b.next()) {
+            (None, None) => return true,
+            (Some(x), Some(y)) => {
+                let equal = match (x.as_os_str().to_str(), y.as_os_str().to_str()) {
+                    (Some(x), Some(y)) => x == y || x.to_lowercase() == y.to_lowercase(),
+                    _ => x == y,
+                };
+                if !equal {
+                    return false;
+                }
+            }
+            _ => return false,
+        }
+    }
+}
+
 pub fn root_dir() -> PathBuf {
     PathBuf::from(std::path::MAIN_SEPARATOR_STR)
 }
 
+/// The root of the current directory, i.e. `C:\` on windows.
+/// Same as [`root_dir`] on unix, or if the current directory can't be determined.
+pub fn current_root() -> PathBuf {
+    #[cfg(windows)]
+    {
+        if let Ok(cwd) = std::env::current_dir() {
+            let root: PathBuf = cwd
+                .components()
+                .take_while(|c| matches!(c, Component::Prefix(_) | Component::RootDir))
+                .collect();
+            if !root.as_os_str().is_empty() {
+                return root;
+            }
+        }
+    }
+    root_dir()
+}
+
 #[easy_ext::ext(PathExt)]
 pub impl<T: AsRef<Path>> T {
     /// Get the owned (lossy) basename of a valid path
@@ -33,10 +311,22 @@ pub impl<T: AsRef<Path>> T {
         basename(path).to_string()
     }
 
-    fn len(&self) -> usize {
+    /// Number of components after normalization: `a/b/../c` has 2.
+    fn component_count(&self) -> usize {
         self.as_ref().normalize().iter().count()
     }
 
+    /// Alias of [`PathExt::component_count`].
+    /// This is **not** the length in bytes, see [`PathExt::byte_len`] for that.
+    fn len(&self) -> usize {
+        self.component_count()
+    }
+
+    /// Length of the path in bytes, as given by [`os_str_to_bytes`]
+    fn byte_len(&self) -> usize {
+        os_str_to_bytes(self.as_ref().as_os_str()).len()
+    }
+
     fn is_hidden(&self) -> bool {
         let path = self.as_ref();
         path.normalize().file_name()
@@ -45,6 +335,7 @@ pub impl<T: AsRef<Path>> T {
     }
 
     /// Prepend base to current path then normalize.
+    /// To resolve against the current directory, use [`PathExt::abs_cwd`].
     ///
     /// # Example
     /// ```rust
@@ -66,6 +357,53 @@ pub impl<T: AsRef<Path>> T {
         .normalize()
     }
 
+    /// [`PathExt::abs`] against the current directory.
+    /// If the current directory can't be determined, this is logged and the path is returned as is.
+    ///
+    /// # Example
+    /// ```rust
+    /// use std::path::Path;
+    /// use cli_boilerplate_automation::bath::PathExt;
+    ///
+    /// assert!(Path::new("a/../b").abs_cwd().is_absolute());
+    /// ```
+    fn abs_cwd(&self) -> PathBuf {
+        let path = self.as_ref();
+        match std::env::current_dir() {
+            Ok(cwd) => path.abs(cwd),
+            Err(e) => {
+                ebog!("Failed to get current directory: {e}");
+                path.to_path_buf()
+            }
+        }
+    }
+
+    /// Get the extension if it is valid UTF-8
+    fn ext_str(&self) -> Option<&str> {
+        self.as_ref().extension().and_then(|e| e.to_str())
+    }
+
+    /// Replace the extension, or strip it entirely (including any trailing '.') if ext is empty.
+    /// A leading '.' in ext is ignored.
+    fn with_ext(&self, ext: &str) -> PathBuf {
+        let path = self.as_ref();
+        let ext = ext.strip_prefix('.').unwrap_or(ext);
+
+        if ext.is_empty() {
+            match path.file_stem() {
+                Some(stem) => path.with_file_name(stem),
+                None => path.to_path_buf(),
+            }
+        } else {
+            path.with_extension(ext)
+        }
+    }
+
+    /// See [`paths_equal_ignore_case`]
+    fn eq_ignore_case(&self, other: impl AsRef<Path>) -> bool {
+        paths_equal_ignore_case(self, other)
+    }
+
     fn is_empty(&self) -> bool {
         let path = self.as_ref();
         path.components().next().is_none()
@@ -100,6 +438,115 @@ pub impl<T: AsRef<Path>> T {
         }
         ret
     }
+
+    /// [`PathExt::normalize`], also returning whether the original path ended in a separator (i.e. `a/b/`).
+    fn normalize_keep_trailing(&self) -> (PathBuf, bool) {
+        let path = self.as_ref();
+        // separators are ASCII, so the last encoded byte is enough on any platform
+        let trailing = path
+            .as_os_str()
+            .as_encoded_bytes()
+            .last()
+            .is_some_and(|&b| std::path::is_separator(b as char));
+        (path.normalize(), trailing)
+    }
+
+    /// [`Path::strip_prefix`] after normalizing both paths, so that `a/./b/c` minus `a/x/../b` is `c`.
+    /// Returns None if base is not a prefix of self.
+    /// Unlike [`PathExt::relative_to`], this never produces `..` components.
+    fn strip_prefix_normalized(&self, base: impl AsRef<Path>) -> Option<PathBuf> {
+        let path = self.as_ref().normalize();
+        let base = base.as_ref().normalize();
+        path.strip_prefix(base).ok().map(Path::to_path_buf)
+    }
+
+    /// [`Path::ancestors`] (starting with self), stopping after boundary.
+    /// If boundary is not an ancestor, all ancestors are yielded.
+    ///
+    /// # Example
+    /// ```rust
+    /// use std::path::Path;
+    /// use cli_boilerplate_automation::bath::PathExt;
+    ///
+    /// let path = Path::new("/home/user/project/src");
+    /// let ancestors: Vec<_> = path.ancestors_until("/home/user").collect();
+    /// assert_eq!(ancestors, ["/home/user/project/src", "/home/user/project", "/home/user"]);
+    /// ```
+    fn ancestors_until(&self, boundary: impl AsRef<Path>) -> impl Iterator<Item = &Path> {
+        let mut done = false;
+        self.as_ref().ancestors().take_while(move |p| {
+            let take = !done;
+            done = done || *p == boundary.as_ref();
+            take
+        })
+    }
+
+    /// Get the path of self relative to base, like python's `os.path.relpath`.
+    /// Both paths are normalized first, keeping leading `..` of relative paths.
+    /// Returns None if the paths don't share a prefix and root (i.e. different drives),
+    /// or if base climbs further out than self, as the result would then depend on the current directory.
+    ///
+    /// # Example
+    /// ```rust
+    /// use std::path::{Path, PathBuf};
+    /// use cli_boilerplate_automation::bath::PathExt;
+    ///
+    /// let path = Path::new("/a/b/c");
+    /// assert_eq!(path.relative_to("/a/d"), Some(PathBuf::from("../b/c")));
+    /// ```
+    fn relative_to(&self, base: impl AsRef<Path>) -> Option<PathBuf> {
+        let path_comps = normalized_components(self.as_ref());
+        let base_comps = normalized_components(base.as_ref());
+
+        let is_root = |c: &&Component| matches!(c, Component::Prefix(_) | Component::RootDir);
+        let path_root = path_comps.iter().take_while(is_root).count();
+        let base_root = base_comps.iter().take_while(is_root).count();
+
+        if path_comps[..path_root] != base_comps[..base_root] {
+            return None;
+        }
+
+        let common = path_comps[path_root..]
+            .iter()
+            .zip(&base_comps[base_root..])
+            .take_while(|(a, b)| a == b)
+            .count();
+
+        let mut ret = PathBuf::new();
+        for c in &base_comps[base_root + common..] {
+            if *c == Component::ParentDir {
+                return None;
+            }
+            ret.push(Component::ParentDir);
+        }
+        for c in &path_comps[path_root + common..] {
+            ret.push(c);
+        }
+
+        if ret.as_os_str().is_empty() {
+            ret.push(Component::CurDir);
+        }
+        Some(ret)
+    }
+}
+
+/// Like [`PathExt::normalize`], but leading `..` of a relative path are kept.
+fn normalized_components(path: &Path) -> Vec<Component<'_>> {
+    let mut ret = Vec::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => match ret.last() {
+                Some(Component::Normal(_)) => {
+                    ret.pop();
+                }
+                Some(Component::Prefix(_) | Component::RootDir) => {}
+                _ => ret.push(component),
+            },
+            _ => ret.push(component),
+        }
+    }
+    ret
 }
 
 /// Cache the expression into a fn() -> &'static Path
@@ -133,22 +580,337 @@ pub fn os_str_to_bytes(string: &OsStr) -> Cow<'_, [u8]> {
     Cow::Owned(bytes)
 }
 
+/// Best effort: non-UTF-8 content is replaced lossily
+#[cfg(not(any(unix, windows)))]
+pub fn os_str_to_bytes(string: &OsStr) -> Cow<'_, [u8]> {
+    match string.to_string_lossy() {
+        Cow::Borrowed(s) => Cow::Borrowed(s.as_bytes()),
+        Cow::Owned(s) => Cow::Owned(s.into_bytes()),
+    }
+}
+
+/// Inverse of [`os_str_to_bytes`].
+/// Always Some on unix.
 #[cfg(unix)]
-pub fn bytes_to_os_string(bytes: Vec<u8>) -> OsString {
+pub fn bytes_to_os_string(bytes: Vec<u8>) -> Option<OsString> {
     use std::os::unix::ffi::OsStringExt;
-    OsString::from_vec(bytes)
+    Some(OsString::from_vec(bytes))
 }
 
+/// Inverse of [`os_str_to_bytes`].
+/// Returns None if bytes is not a whole number of UTF-16 code units.
 #[cfg(windows)]
-pub fn bytes_to_os_string(bytes: Vec<u8>) -> OsString {
+pub fn bytes_to_os_string(bytes: Vec<u8>) -> Option<OsString> {
     use std::os::windows::ffi::OsStringExt;
 
-    debug_assert!(bytes.len() % 2 == 0, "invalid UTF-16 byte length");
+    if !bytes.len().is_multiple_of(2) {
+        return None;
+    }
 
     let wide: Vec<u16> = bytes
     .chunks_exact(2)
     .map(|c| u16::from_le_bytes([c[0], c[1]]))
     .collect();
 
-    OsString::from_wide(&wide)
+    Some(OsString::from_wide(&wide))
+}
+
+/// Inverse of [`os_str_to_bytes`].
+/// Best effort: invalid UTF-8 is replaced lossily.
+#[cfg(not(any(unix, windows)))]
+pub fn bytes_to_os_string(bytes: Vec<u8>) -> Option<OsString> {
+    let string = match String::from_utf8(bytes) {
+        Ok(s) => s,
+        Err(e) => String::from_utf8_lossy(e.as_bytes()).into_owned(),
+    };
+    Some(string.into())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn relative_to_sibling() {
+        assert_eq!(
+            Path::new("/a/b/c").relative_to("/a/d"),
+            Some(PathBuf::from("../b/c"))
+        );
+    }
+
+    #[test]
+    fn relative_to_deeper_and_shallower() {
+        assert_eq!(
+            Path::new("/a/b/c/d").relative_to("/a/b"),
+            Some(PathBuf::from("c/d"))
+        );
+        assert_eq!(
+            Path::new("/a").relative_to("/a/b/c"),
+            Some(PathBuf::from("../.."))
+        );
+        assert_eq!(
+            Path::new("a/./b/../c").relative_to("a"),
+            Some(PathBuf::from("c"))
+        );
+    }
+
+    #[test]
+    fn relative_to_identical() {
+        assert_eq!(Path::new("/a/b").relative_to("/a/b/"), Some(PathBuf::from(".")));
+        assert_eq!(Path::new("/").relative_to("/"), Some(PathBuf::from(".")));
+    }
+
+    #[test]
+    fn relative_to_mismatched_root() {
+        assert_eq!(Path::new("/a/b").relative_to("a"), None);
+    }
+
+    #[test]
+    fn relative_to_leading_parent() {
+        assert_eq!(
+            Path::new("../a").relative_to("b"),
+            Some(PathBuf::from("../../a"))
+        );
+        assert_eq!(
+            Path::new("../a").relative_to("../b/c"),
+            Some(PathBuf::from("../../a"))
+        );
+        assert_eq!(
+            Path::new("/../a").relative_to("/b"),
+            Some(PathBuf::from("../a"))
+        );
+        // depends on the name of the current directory
+        assert_eq!(Path::new("x").relative_to("../y"), None);
+        assert_eq!(Path::new("../x").relative_to("../../y"), None);
+    }
+
+    #[test]
+    fn sanitize_filename_matrix() {
+        let cases = [
+            ("report.txt", "report.txt"),
+            ("a/b:c*d?", "a_b_c_d_"),
+            ("<x>|\"y\"", "_x___y_"),
+            ("tab\there\n", "tab_here_"),
+            ("trailing. . ", "trailing"),
+            ("", "_"),
+            ("...", "_"),
+            ("CON", "CON_"),
+            ("con", "con_"),
+            ("NUL.txt", "NUL_.txt"),
+            ("aux.tar.gz", "aux_.tar.gz"),
+            ("COM1", "COM1_"),
+            ("lpt9.log", "lpt9_.log"),
+            ("COM0", "COM0"),
+            ("CONSOLE", "CONSOLE"),
+            ("LPT10", "LPT10"),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(sanitize_filename(input), expected, "input: {input:?}");
+        }
+    }
+
+    #[test]
+    fn unique_path_suffixes_stem() {
+        let dir = std::env::temp_dir().join(format!("cba_unique_path_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let file = dir.join("file.txt");
+        assert_eq!(unique_path(&file), file);
+
+        std::fs::write(&file, "").unwrap();
+        assert_eq!(unique_path(&file), dir.join("file (1).txt"));
+
+        std::fs::write(dir.join("file (1).txt"), "").unwrap();
+        assert_eq!(unique_path(&file), dir.join("file (2).txt"));
+
+        let bare = dir.join("bare");
+        std::fs::write(&bare, "").unwrap();
+        assert_eq!(unique_path(&bare), dir.join("bare (1)"));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn os_string_bytes_round_trip() {
+        let s = OsStr::new("some/path");
+        let bytes = os_str_to_bytes(s).into_owned();
+        assert_eq!(bytes_to_os_string(bytes).as_deref(), Some(s));
+    }
+
+    #[test]
+    fn canonicalize_lenient_missing_tail() {
+        let tmp = std::env::temp_dir().canonicalize().unwrap();
+        assert_eq!(
+            canonicalize_lenient(tmp.join("cba_missing/./x/../file.txt")),
+            tmp.join("cba_missing/file.txt")
+        );
+        assert_eq!(canonicalize_lenient(&tmp), tmp);
+    }
+
+    #[test]
+    fn glob_match_star() {
+        assert!(glob_match("*.rs", "main.rs"));
+        assert!(!glob_match("*.rs", ".rs.rs"));
+        assert!(!glob_match("*.rs", "main.rsx"));
+        assert!(!glob_match("*.rs", "src/main.rs"));
+        assert!(glob_match("src/*", "src/main.rs"));
+        assert!(glob_match("*", ""));
+    }
+
+    #[test]
+    fn glob_match_double_star() {
+        assert!(glob_match("src/**", "src/a/b/c.rs"));
+        assert!(glob_match("src/**", "src/"));
+        assert!(glob_match("**/*.rs", "main.rs"));
+        assert!(glob_match("**/*.rs", "a/b/c.rs"));
+        assert!(glob_match("src/**/mod.rs", "src/mod.rs"));
+        assert!(!glob_match("src/**", "lib/a.rs"));
+    }
+
+    #[test]
+    fn glob_match_question_and_class() {
+        assert!(glob_match("file?.txt", "file1.txt"));
+        assert!(!glob_match("file?.txt", "file.txt"));
+        assert!(glob_match("[abc].log", "b.log"));
+        assert!(!glob_match("[abc].log", "d.log"));
+        assert!(glob_match("[a-c]x", "cx"));
+        assert!(glob_match("[!a-c]x", "dx"));
+        assert!(!glob_match("[!a-c]x", "ax"));
+        assert!(glob_match("[]]", "]"));
+        assert!(glob_match("[x", "[x"));
+    }
+
+    #[test]
+    fn glob_match_leading_dot() {
+        assert!(!glob_match("*", ".bashrc"));
+        assert!(!glob_match("?bashrc", ".bashrc"));
+        assert!(!glob_match("**/*.rs", "a/.hidden/c.rs"));
+        assert!(!glob_match("a/*", "a/.git"));
+        assert!(glob_match(".*", ".bashrc"));
+        assert!(glob_match("a/.*", "a/.git"));
+        assert!(glob_match("*.*", "archive.tar.gz"));
+    }
+
+    #[test]
+    fn ext_accessors() {
+        assert_eq!(Path::new("a/b.tar.gz").ext_str(), Some("gz"));
+        assert_eq!(Path::new("a/b").ext_str(), None);
+        assert_eq!(Path::new(".bashrc").ext_str(), None);
+
+        assert_eq!(Path::new("a/b.txt").with_ext("md"), PathBuf::from("a/b.md"));
+        assert_eq!(Path::new("a/b.txt").with_ext(".md"), PathBuf::from("a/b.md"));
+        assert_eq!(Path::new("a/b").with_ext("md"), PathBuf::from("a/b.md"));
+        assert_eq!(Path::new("a/b.txt").with_ext(""), PathBuf::from("a/b"));
+        assert_eq!(Path::new("a/b.").with_ext(""), PathBuf::from("a/b"));
+        assert_eq!(Path::new(".bashrc").with_ext(""), PathBuf::from(".bashrc"));
+    }
+
+    #[test]
+    fn component_count_and_byte_len() {
+        assert_eq!(Path::new("a/b/../c").component_count(), 2);
+        assert_eq!(Path::new("/a/./b").component_count(), 3);
+        assert_eq!(Path::new("a/b/../c").byte_len(), 8);
+    }
+
+    #[test]
+    fn equal_ignore_case() {
+        assert!(paths_equal_ignore_case("a/Foo.txt", "a/foo.TXT"));
+        assert!(paths_equal_ignore_case("/Ä/./b", "/ä/b/"));
+        assert!(Path::new("Dir/Ω").eq_ignore_case("dir/ω"));
+        assert!(!paths_equal_ignore_case("a/foo", "a/foo/bar"));
+        assert!(!paths_equal_ignore_case("/a", "a"));
+    }
+
+    #[test]
+    fn common_ancestor_cases() {
+        let empty: [&str; 0] = [];
+        assert_eq!(common_ancestor(&empty), None);
+        assert_eq!(common_ancestor(&["/a/b/c"]), Some(PathBuf::from("/a/b/c")));
+        assert_eq!(common_ancestor(&["/a/b", "/a/b/"]), Some(PathBuf::from("/a/b")));
+        assert_eq!(
+            common_ancestor(&["/a/b/c.txt", "/a/b/d/e.txt", "/a/./b/f"]),
+            Some(PathBuf::from("/a/b"))
+        );
+        assert_eq!(common_ancestor(&["/a", "/b"]), Some(PathBuf::from("/")));
+        assert_eq!(common_ancestor(&["a/b", "c/d"]), None);
+        assert_eq!(common_ancestor(&["/a/b", "a/b"]), None);
+    }
+
+    #[test]
+    fn normalize_keep_trailing_slash() {
+        assert_eq!(
+            Path::new("a/b/").normalize_keep_trailing(),
+            (PathBuf::from("a/b"), true)
+        );
+        assert_eq!(
+            Path::new("a/b").normalize_keep_trailing(),
+            (PathBuf::from("a/b"), false)
+        );
+        assert_eq!(
+            Path::new("./a/../b//").normalize_keep_trailing(),
+            (PathBuf::from("b"), true)
+        );
+        assert_eq!(
+            Path::new("/").normalize_keep_trailing(),
+            (PathBuf::from("/"), true)
+        );
+        assert_eq!(
+            Path::new("").normalize_keep_trailing(),
+            (PathBuf::new(), false)
+        );
+    }
+
+    #[test]
+    fn strip_prefix_normalized_cases() {
+        assert_eq!(
+            Path::new("a/./b/c").strip_prefix_normalized("a/x/../b"),
+            Some(PathBuf::from("c"))
+        );
+        assert_eq!(
+            Path::new("/root/dir/").strip_prefix_normalized("/root/./dir"),
+            Some(PathBuf::new())
+        );
+        assert_eq!(Path::new("/a/bc").strip_prefix_normalized("/a/b"), None);
+        assert_eq!(Path::new("a/b").strip_prefix_normalized("/a"), None);
+    }
+
+    #[test]
+    fn ancestors_until_boundary() {
+        let path = Path::new("/a/b/c");
+        let collect = |boundary: &str| path.ancestors_until(boundary).collect::<Vec<_>>();
+        assert_eq!(collect("/a/b/c"), [Path::new("/a/b/c")]);
+        assert_eq!(collect("/a/"), ["/a/b/c", "/a/b", "/a"].map(Path::new));
+        assert_eq!(collect("/x"), ["/a/b/c", "/a/b", "/a", "/"].map(Path::new));
+    }
+
+    #[test]
+    fn find_upwards_markers() {
+        let root = std::env::temp_dir().join(format!("cba_bath_upwards_{}", std::process::id()));
+        let nested = root.join("a/b/c");
+        std::fs::create_dir_all(&nested).unwrap();
+        std::fs::write(root.join("Cargo.toml"), "").unwrap();
+        std::fs::create_dir(root.join("a/.git")).unwrap();
+
+        assert_eq!(find_upwards(&nested, "Cargo.toml"), Some(root.clone()));
+        assert_eq!(find_upwards(&nested, ".git"), Some(root.join("a")));
+        assert_eq!(find_upwards(root.join("a"), ".git"), Some(root.join("a")));
+        assert_eq!(
+            find_upwards_any(&nested, &["missing", "Cargo.toml"]),
+            Some(root.clone())
+        );
+        assert_eq!(find_upwards(&nested, "cba_find_upwards_missing"), None);
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn split_ext_dotfiles() {
+        assert_eq!(split_ext("archive"), ["archive", ""]);
+        assert_eq!(split_ext("a.tar.gz"), ["a.tar", "gz"]);
+        assert_eq!(split_ext(".gitignore"), [".gitignore", ""]);
+        assert_eq!(split_ext(".tar.gz"), [".tar", "gz"]);
+        assert_eq!(split_ext("file."), ["file", ""]);
+        assert_eq!(split_ext("."), [".", ""]);
+        assert_eq!(split_ext(".."), ["..", ""]);
+    }
 }
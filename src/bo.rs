@@ -1,49 +1,229 @@
 //! IO
 
-use std::{error::Error, fs, io, path::Path};
+use std::{
+    error::Error,
+    fs, io,
+    path::{Path, PathBuf},
+};
 
 use crate::{ebog, get_or_err};
 
 // ------------ File read/write (bile) -------------
+/// [`dump_type_ext`], replacing any extension of path with toml
 pub fn dump_type<T, E: Error>(
     path: impl AsRef<Path>,
     input: &T,
     string_maker: impl FnOnce(&T) -> Result<String, E>,
 ) -> bool {
     let path = path.as_ref().with_extension("toml");
+    dump_type_ext(path, "toml", input, string_maker)
+}
+
+/// Serialize input with string_maker and write it to path.
+/// fmt_ext is used as the extension only if path doesn't already have one.
+pub fn dump_type_ext<T, E: Error>(
+    path: impl AsRef<Path>,
+    fmt_ext: &str,
+    input: &T,
+    string_maker: impl FnOnce(&T) -> Result<String, E>,
+) -> bool {
+    dump_type_inner(path.as_ref(), fmt_ext, false, input, string_maker)
+}
+
+/// [`dump_type_ext`], but first move any existing file to `<path>.bak`, replacing a previous backup.
+/// If the write fails, the backup is restored.
+pub fn dump_type_backup<T, E: Error>(
+    path: impl AsRef<Path>,
+    fmt_ext: &str,
+    input: &T,
+    string_maker: impl FnOnce(&T) -> Result<String, E>,
+) -> bool {
+    dump_type_inner(path.as_ref(), fmt_ext, true, input, string_maker)
+}
+
+fn dump_type_inner<T, E: Error>(
+    path: &Path,
+    fmt_ext: &str,
+    backup: bool,
+    input: &T,
+    string_maker: impl FnOnce(&T) -> Result<String, E>,
+) -> bool {
+    let path = with_default_ext(path, fmt_ext);
     let type_name = std::any::type_name::<T>().rsplit("::").next().unwrap();
     let error_prefix = format!("Failed to save {type_name} to {}", path.to_string_lossy());
 
     let content = get_or_err!(string_maker(input), error_prefix);
-    match fs::write(path, content) {
+
+    let backup_path = (backup && path.exists()).then(|| {
+        let mut backup_path = path.clone().into_os_string();
+        backup_path.push(".bak");
+        PathBuf::from(backup_path)
+    });
+    if let Some(ref backup_path) = backup_path {
+        get_or_err!(fs::rename(&path, backup_path), error_prefix);
+    }
+
+    match fs::write(&path, content) {
         Ok(_) => true,
         Err(e) => {
             ebog!("{error_prefix}: {e}");
+            if let Some(backup_path) = backup_path
+                && let Err(e) = fs::rename(&backup_path, &path)
+            {
+                ebog!("Failed to restore {backup_path:?}: {e}");
+            }
             false
         }
     }
 }
 
-/// Returns none if file could not be found/read/parsed
+/// Outcome of [`dump_type_if_changed`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DumpResult {
+    Written,
+    /// The file already had the serialized content, and was left untouched
+    Unchanged,
+    /// Serializing or writing failed (and was logged)
+    Failed,
+}
+
+/// [`dump_type_ext`], but only write if the serialized content differs from the existing file,
+/// so that its mtime isn't bumped (and file watchers aren't triggered) needlessly.
+/// A missing or unreadable file is written.
+pub fn dump_type_if_changed<T, E: Error>(
+    path: impl AsRef<Path>,
+    fmt_ext: &str,
+    input: &T,
+    string_maker: impl FnOnce(&T) -> Result<String, E>,
+) -> DumpResult {
+    let path = with_default_ext(path.as_ref(), fmt_ext);
+    let type_name = std::any::type_name::<T>().rsplit("::").next().unwrap();
+    let error_prefix = format!("Failed to save {type_name} to {}", path.to_string_lossy());
+
+    let content = match string_maker(input) {
+        Ok(content) => content,
+        Err(e) => {
+            ebog!("{error_prefix}: {e}");
+            return DumpResult::Failed;
+        }
+    };
+
+    if fs::read(&path).is_ok_and(|existing| existing == content.as_bytes()) {
+        return DumpResult::Unchanged;
+    }
+
+    match fs::write(&path, content) {
+        Ok(_) => DumpResult::Written,
+        Err(e) => {
+            ebog!("{error_prefix}: {e}");
+            DumpResult::Failed
+        }
+    }
+}
+
+/// [`load_type_ext`], replacing any extension of path with toml
 pub fn load_type<T, E: Error>(
     path: impl AsRef<Path>,
     str_loader: impl FnOnce(&str) -> Result<T, E>, // pass a closure here if u need to satisfy hrtb
 ) -> Option<T> {
     let path = path.as_ref().with_extension("toml");
+    load_type_ext(path, "toml", str_loader)
+}
+
+/// Returns none if file could not be found/read/parsed
+/// fmt_ext is used as the extension only if path doesn't already have one.
+pub fn load_type_ext<T, E: Error>(
+    path: impl AsRef<Path>,
+    fmt_ext: &str,
+    str_loader: impl FnOnce(&str) -> Result<T, E>,
+) -> Option<T> {
+    load_type_inner(path.as_ref(), fmt_ext, None, str_loader)
+}
+
+/// [`load_type_ext`], but fail if the file is larger than max_bytes
+pub fn load_type_capped<T, E: Error>(
+    path: impl AsRef<Path>,
+    fmt_ext: &str,
+    max_bytes: u64,
+    str_loader: impl FnOnce(&str) -> Result<T, E>,
+) -> Option<T> {
+    load_type_inner(path.as_ref(), fmt_ext, Some(max_bytes), str_loader)
+}
+
+fn load_type_inner<T, E: Error>(
+    path: &Path,
+    fmt_ext: &str,
+    max_bytes: Option<u64>,
+    str_loader: impl FnOnce(&str) -> Result<T, E>,
+) -> Option<T> {
+    let path = with_default_ext(path, fmt_ext);
     let type_name = std::any::type_name::<T>().rsplit("::").next().unwrap();
     let error_prefix = format!("Failed to load {type_name} from {}", path.to_string_lossy());
 
-    let mut file = get_or_err!(fs::File::open(path), error_prefix);
-
-    let mut contents = String::new();
-    get_or_err!(
-        io::Read::read_to_string(&mut file, &mut contents),
-        error_prefix
-    );
+    let bytes = match max_bytes {
+        Some(max_bytes) => read_capped(&path, max_bytes),
+        None => fs::read(&path),
+    };
+    let contents = get_or_err!(bytes.and_then(decode_text), error_prefix);
 
     Some(get_or_err!(str_loader(&contents), error_prefix))
 }
 
+/// [`dump_type`] using toml
+#[cfg(feature = "toml")]
+pub fn save_toml<T: serde::Serialize>(path: impl AsRef<Path>, input: &T) -> bool {
+    dump_type(path, input, |v| toml::to_string(v))
+}
+
+/// [`load_type`] using toml
+#[cfg(feature = "toml")]
+pub fn load_toml<T: serde::de::DeserializeOwned>(path: impl AsRef<Path>) -> Option<T> {
+    load_type(path, |s| toml::from_str(s))
+}
+
+/// [`dump_type_ext`] using (pretty) json
+#[cfg(feature = "json")]
+pub fn save_json<T: serde::Serialize>(path: impl AsRef<Path>, input: &T) -> bool {
+    dump_type_ext(path, "json", input, |v| serde_json::to_string_pretty(v))
+}
+
+/// [`load_type_ext`] using json
+#[cfg(feature = "json")]
+pub fn load_json<T: serde::de::DeserializeOwned>(path: impl AsRef<Path>) -> Option<T> {
+    load_type_ext(path, "json", |s| serde_json::from_str(s))
+}
+
+/// Load T from path, or write out and return the default if the file doesn't exist.
+/// Like [`load_type`], any extension of path is replaced with toml.
+/// If the file exists but can't be read or parsed, the error is logged and the default is returned without overwriting the file.
+pub fn load_or_init<T: Default, E1: Error, E2: Error>(
+    path: impl AsRef<Path>,
+    str_loader: impl FnOnce(&str) -> Result<T, E1>,
+    string_maker: impl FnOnce(&T) -> Result<String, E2>,
+) -> T {
+    let path = path.as_ref().with_extension("toml");
+
+    match fs::symlink_metadata(&path) {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            let default = T::default();
+            if let Some(p) = path.parent() {
+                let _ = fs::create_dir_all(p); // dump_type reports failure
+            }
+            dump_type_ext(&path, "toml", &default, string_maker);
+            default
+        }
+        _ => load_type_ext(&path, "toml", str_loader).unwrap_or_default(),
+    }
+}
+
+fn with_default_ext(path: &Path, ext: &str) -> PathBuf {
+    if path.extension().is_some() {
+        path.to_path_buf()
+    } else {
+        path.with_extension(ext)
+    }
+}
+
 pub fn write_str(path: &Path, contents: &str) -> io::Result<()> {
     if let Some(p) = path.parent() {
         std::fs::create_dir_all(p)?; // normalize should ensure parent always works
@@ -53,10 +233,185 @@ pub fn write_str(path: &Path, contents: &str) -> io::Result<()> {
     Ok(())
 }
 
+/// Append contents to path, creating it (and its parents) if needed.
+/// The file is opened with O_APPEND, so concurrent appenders never overwrite each other,
+/// but on unix a single append is only guaranteed not to interleave with other processes' when it is under PIPE_BUF bytes.
+pub fn append_str(path: &Path, contents: &str) -> io::Result<()> {
+    if let Some(p) = path.parent() {
+        std::fs::create_dir_all(p)?;
+    }
+    let mut file = fs::OpenOptions::new().create(true).append(true).open(path)?;
+    // one write call so the append is atomic where the OS allows
+    io::Write::write_all(&mut file, contents.as_bytes())?;
+
+    Ok(())
+}
+
+/// Like [`write_str`], but write to a temporary file in the same directory first, then rename it over path.
+/// The target will contain either the old or the new contents, even if the process dies mid-write.
+pub fn write_str_atomic(path: &Path, contents: &str) -> io::Result<()> {
+    if let Some(p) = path.parent() {
+        std::fs::create_dir_all(p)?;
+    }
+    let name = path.file_name().ok_or_else(|| {
+        io::Error::new(io::ErrorKind::InvalidInput, format!("{path:?} has no file name"))
+    })?;
+
+    // same directory so that rename doesn't cross devices
+    let mut tmp_name = std::ffi::OsString::from(".");
+    tmp_name.push(name);
+    tmp_name.push(format!(".{}.tmp", std::process::id()));
+    let tmp = path.with_file_name(tmp_name);
+
+    let result = (|| {
+        let mut file = fs::File::create(&tmp)?;
+        io::Write::write_all(&mut file, contents.as_bytes())?;
+        file.sync_all()?;
+        fs::rename(&tmp, path)
+    })();
+
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    result
+}
+
+/// Read a file to a string, failing if it is larger than max_bytes.
+/// The limit is checked against the metadata up front, and enforced during the read for growing files and pipes.
+pub fn read_to_string_capped(path: impl AsRef<Path>, max_bytes: u64) -> io::Result<String> {
+    let bytes = read_capped(path.as_ref(), max_bytes)?;
+    String::from_utf8(bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+fn read_capped(path: &Path, max_bytes: u64) -> io::Result<Vec<u8>> {
+    let too_large = || {
+        io::Error::new(
+            io::ErrorKind::FileTooLarge,
+            format!("{path:?} exceeds the limit of {max_bytes} bytes"),
+        )
+    };
+
+    let file = fs::File::open(path)?;
+    if let Ok(metadata) = file.metadata()
+        && metadata.is_file()
+        && metadata.len() > max_bytes
+    {
+        return Err(too_large());
+    }
+
+    let mut bytes = Vec::new();
+    // read one past the limit to detect overflow
+    file.take(max_bytes.saturating_add(1))
+        .read_to_end(&mut bytes)?;
+
+    if bytes.len() as u64 > max_bytes {
+        return Err(too_large());
+    }
+    Ok(bytes)
+}
+
+/// Read a text file to a string, handling a leading byte order mark (see [`decode_text`])
+pub fn read_to_string_no_bom(path: impl AsRef<Path>) -> io::Result<String> {
+    decode_text(fs::read(path)?)
+}
+
+/// Decode text as UTF-8, or as UTF-16 if it starts with a UTF-16 (LE/BE) byte order mark.
+/// A leading BOM is removed from the result.
+pub fn decode_text(bytes: Vec<u8>) -> io::Result<String> {
+    let invalid = |e: &dyn std::fmt::Display| io::Error::new(io::ErrorKind::InvalidData, e.to_string());
+
+    let from_utf16 = |bytes: &[u8], from_bytes: fn([u8; 2]) -> u16| {
+        if !bytes.len().is_multiple_of(2) {
+            return Err(invalid(&"Invalid UTF-16: odd number of bytes"));
+        }
+        let wide: Vec<u16> = bytes
+            .chunks_exact(2)
+            .map(|c| from_bytes([c[0], c[1]]))
+            .collect();
+        String::from_utf16(&wide).map_err(|e| invalid(&e))
+    };
+
+    match bytes.as_slice() {
+        [0xEF, 0xBB, 0xBF, rest @ ..] => std::str::from_utf8(rest)
+            .map(str::to_string)
+            .map_err(|e| invalid(&e)),
+        [0xFF, 0xFE, rest @ ..] => from_utf16(rest, u16::from_le_bytes),
+        [0xFE, 0xFF, rest @ ..] => from_utf16(rest, u16::from_be_bytes),
+        _ => String::from_utf8(bytes).map_err(|e| invalid(&e)),
+    }
+}
+
+/// Read the lines of a text file, without their line endings (`\n` or `\r\n`).
+/// The file is decoded like [`read_to_string_no_bom`].
+/// Returns None (after logging) if the file can't be read or decoded.
+pub fn read_lines(path: impl AsRef<Path>) -> Option<Vec<String>> {
+    let path = path.as_ref();
+    let error_prefix = format!("Failed to read {path:?}");
+
+    let contents = get_or_err!(read_to_string_no_bom(path), error_prefix);
+    Some(contents.lines().map(str::to_string).collect())
+}
+
+/// Read a file, returning None (after logging) on failure
+pub fn read_bytes(path: impl AsRef<Path>) -> Option<Vec<u8>> {
+    let path = path.as_ref();
+    let error_prefix = format!("Failed to read {path:?}");
+
+    Some(get_or_err!(fs::read(path), error_prefix))
+}
+
+// --------- HASH ------------
+const FNV_OFFSET: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+
+fn fnv1a(mut hash: u64, bytes: &[u8]) -> u64 {
+    for &b in bytes {
+        hash ^= b as u64;
+        hash = hash.wrapping_mul(FNV_PRIME);
+    }
+    hash
+}
+
+/// 64-bit FNV-1a hash of s. Fast, stable across runs and platforms, but not cryptographic.
+pub fn hash_str(s: &str) -> u64 {
+    fnv1a(FNV_OFFSET, s.as_bytes())
+}
+
+/// [`hash_str`] of the contents of path, streamed so that large files aren't loaded into memory
+pub fn hash_file(path: impl AsRef<Path>) -> Option<u64> {
+    let path = path.as_ref();
+    let error_prefix = format!("Failed to hash {path:?}");
+
+    let file = get_or_err!(fs::File::open(path), error_prefix);
+    let mut reader = io::BufReader::new(file);
+    let mut hash = FNV_OFFSET;
+    loop {
+        let buf = get_or_err!(reader.fill_buf(), error_prefix);
+        if buf.is_empty() {
+            return Some(hash);
+        }
+        hash = fnv1a(hash, buf);
+        let len = buf.len();
+        reader.consume(len);
+    }
+}
+
+/// SHA-256 digest of the contents of path, streamed so that large files aren't loaded into memory
+#[cfg(feature = "sha2")]
+pub fn sha256_file(path: impl AsRef<Path>) -> Option<[u8; 32]> {
+    use sha2::Digest;
+    let path = path.as_ref();
+    let error_prefix = format!("Failed to hash {path:?}");
+
+    let mut file = get_or_err!(fs::File::open(path), error_prefix);
+    let mut hasher = sha2::Sha256::new();
+    get_or_err!(io::copy(&mut file, &mut hasher), error_prefix);
+    Some(hasher.finalize().into())
+}
+
 // --------- READER ------------
-// todo: decide on how to handle max chunks
 use log::{error, warn};
-use std::{io::{BufRead, Read}};
+use std::io::{BufRead, Read};
 
 #[derive(Debug, thiserror::Error)]
 #[non_exhaustive]
@@ -67,18 +422,61 @@ pub enum MapReaderError<E> {
     Custom(E),
 }
 
+/// Split reader on delim.
+/// For newline-delimited text use [`read_to_lines`], which also handles `\r\n`.
 pub fn read_to_chunks<R: Read>(reader: R, delim: char) -> std::io::Split<std::io::BufReader<R>> {
     io::BufReader::new(reader).split(delim as u8)
 }
 
-// do not use for newlines as it doesn't handle \r!
-// todo: warn about this in config
+/// Split reader on '\n', stripping a trailing '\r' from each line (like [`BufRead::lines`]).
+pub fn read_to_lines<R: Read>(reader: R) -> impl Iterator<Item = io::Result<Vec<u8>>> {
+    read_to_chunks(reader, '\n').map(|chunk| {
+        chunk.map(|mut bytes| {
+            if bytes.last() == Some(&b'\r') {
+                bytes.pop();
+            }
+            bytes
+        })
+    })
+}
+
 // note: stream means wrapping with closure passed stream::unfold and returning f() inside
 
-pub fn map_chunks<const INVALID_FAIL: bool, E>(iter: impl Iterator<Item = std::io::Result<Vec<u8>>>, mut f: impl FnMut(String) -> Result<(), E>) -> Result<(), MapReaderError<E>>
+/// What to do with chunks that aren't valid UTF-8
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum InvalidMode {
+    /// Log and skip the chunk
+    #[default]
+    Skip,
+    /// Log and abort with [`MapReaderError::ChunkError`]
+    Fail,
+    /// Replace invalid sequences with U+FFFD
+    Lossy,
+}
+
+/// Number of chunks passed on and skipped by [`map_chunks`]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ReadStats {
+    /// Chunks passed to f (including lossily decoded ones)
+    pub processed: usize,
+    /// Chunks skipped due to invalid UTF-8
+    pub skipped: usize,
+}
+
+/// Decode and pass each chunk from [`read_to_chunks`] or [`read_to_lines`] to f.
+/// Reading stops with [`MapReaderError::ChunkError`] once max chunks (default u32::MAX) have been read.
+/// On success, returns how many chunks were processed and skipped.
+pub fn map_chunks<E>(iter: impl Iterator<Item = std::io::Result<Vec<u8>>>, max: Option<usize>, invalid: InvalidMode, mut f: impl FnMut(String) -> Result<(), E>) -> Result<ReadStats, MapReaderError<E>>
 {
+    map_chunks_inner(iter, max, invalid, |_, s| f(s))
+}
+
+fn map_chunks_inner<E>(iter: impl Iterator<Item = std::io::Result<Vec<u8>>>, max: Option<usize>, invalid: InvalidMode, mut f: impl FnMut(usize, String) -> Result<(), E>) -> Result<ReadStats, MapReaderError<E>>
+{
+    let max = max.unwrap_or(u32::MAX as usize);
+    let mut stats = ReadStats::default();
     for (i, chunk_result) in iter.enumerate() {
-        if i == u32::MAX as usize {
+        if i == max {
             warn!("Reached maximum segment limit, stopping input read");
             return Err(MapReaderError::ChunkError(i));
         }
@@ -91,50 +489,361 @@ pub fn map_chunks<const INVALID_FAIL: bool, E>(iter: impl Iterator<Item = std::i
             }
         };
 
-        match String::from_utf8(chunk) {
-            Ok(s) => {
-                if let Err(e) = f(s) {
-                    return Err(MapReaderError::Custom(e));
-                }
-            }
-            Err(e) => {
-                error!("Invalid UTF-8 in stdin at byte {}: {}", e.utf8_error().valid_up_to(), e);
-                // Skip but continue reading
-                if INVALID_FAIL {
-                    return Err(MapReaderError::ChunkError(i));
-                } else {
-                    continue
+        let s = match String::from_utf8(chunk) {
+            Ok(s) => s,
+            Err(e) => match invalid {
+                InvalidMode::Lossy => String::from_utf8_lossy(e.as_bytes()).into_owned(),
+                InvalidMode::Skip | InvalidMode::Fail => {
+                    error!("Invalid UTF-8 in stdin at byte {}: {}", e.utf8_error().valid_up_to(), e);
+                    if invalid == InvalidMode::Fail {
+                        return Err(MapReaderError::ChunkError(i));
+                    } else {
+                        stats.skipped += 1;
+                        continue
+                    }
                 }
-            }
+            },
+        };
+
+        if let Err(e) = f(i + 1, s) {
+            return Err(MapReaderError::Custom(e));
         }
+        stats.processed += 1;
     }
-    Ok(())
+    Ok(stats)
+}
+
+/// [`map_chunks`] over the lines of reader, `\n` and `\r\n` are both accepted as line endings.
+pub fn map_reader_lines<E>(reader: impl Read, max: Option<usize>, invalid: InvalidMode, f: impl FnMut(String) -> Result<(), E>) -> Result<ReadStats, MapReaderError<E>> {
+    map_chunks(read_to_lines(reader), max, invalid, f)
 }
 
+/// [`map_reader_lines`], also passing the (1-based) line number to f.
+/// Skipped lines are still counted, so numbers match the input.
+pub fn map_reader_lines_numbered<E>(reader: impl Read, max: Option<usize>, invalid: InvalidMode, f: impl FnMut(usize, String) -> Result<(), E>) -> Result<ReadStats, MapReaderError<E>> {
+    map_chunks_inner(read_to_lines(reader), max, invalid, f)
+}
 
-pub fn map_reader_lines<const INVALID_FAIL: bool, E>(reader: impl Read, mut f: impl FnMut(String) -> Result<(), E>) -> Result<(), MapReaderError<E>> {
-    let buf_reader = io::BufReader::new(reader);
+// --------- STDIN ------------
 
-    for (i, line) in buf_reader.lines().enumerate() {
-        if i == u32::MAX as usize {
-            eprintln!("Reached maximum line limit, stopping input read");
-            return Err(MapReaderError::ChunkError(i));
-        }
-        match line {
-            Ok(l) => {
-                if let Err(e) = f(l) {
-                    return Err(MapReaderError::Custom(e));
-                }
-            }
-            Err(e) => {
-                eprintln!("Error reading line: {}", e);
-                if INVALID_FAIL {
-                    return Err(MapReaderError::ChunkError(i));
-                } else {
-                    continue
-                }
+/// [`map_reader_lines`] over stdin, skipping invalid UTF-8
+pub fn stdin_lines<E>(f: impl FnMut(String) -> Result<(), E>) -> Result<ReadStats, MapReaderError<E>> {
+    map_reader_lines(io::stdin().lock(), None, InvalidMode::default(), f)
+}
+
+/// [`map_chunks`] over stdin split on delim, skipping invalid UTF-8
+pub fn stdin_chunks<E>(delim: char, f: impl FnMut(String) -> Result<(), E>) -> Result<ReadStats, MapReaderError<E>> {
+    map_chunks(read_to_chunks(io::stdin().lock(), delim), None, InvalidMode::default(), f)
+}
+
+/// Read all of stdin to a string, returning None (after logging) on failure or invalid UTF-8
+pub fn read_stdin_to_string() -> Option<String> {
+    let mut s = String::new();
+    get_or_err!(io::stdin().lock().read_to_string(&mut s), "Failed to read stdin");
+    Some(s)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn test_dir(name: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("cba_bo_{name}_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn read_lines_and_bytes() {
+        let dir = test_dir("read_lines");
+        let path = dir.join("lines.txt");
+        write_str(&path, "a\r\nb\n\nc").unwrap();
+
+        assert_eq!(read_lines(&path).unwrap(), ["a", "b", "", "c"]);
+        assert_eq!(read_bytes(&path).unwrap(), b"a\r\nb\n\nc");
+        assert_eq!(read_lines(dir.join("missing")), None);
+        assert_eq!(read_bytes(dir.join("missing")), None);
+
+        write_str(&path, "\u{feff}a\nb").unwrap();
+        assert_eq!(read_lines(&path).unwrap(), ["a", "b"]);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn write_str_atomic_replaces() {
+        let dir = test_dir("atomic");
+        let path = dir.join("nested/config.toml");
+
+        write_str_atomic(&path, "a = 1").unwrap();
+        write_str_atomic(&path, "a = 2").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "a = 2");
+        // no leftover temp files
+        assert_eq!(fs::read_dir(path.parent().unwrap()).unwrap().count(), 1);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn dump_and_load_ext() {
+        let dir = test_dir("ext");
+        fs::create_dir_all(&dir).unwrap();
+        let parse = |s: &str| s.parse::<i32>();
+        let show = |x: &i32| Ok::<_, std::fmt::Error>(x.to_string());
+
+        assert!(dump_type_ext(dir.join("num"), "json", &5, show));
+        assert!(dir.join("num.json").exists());
+        assert_eq!(load_type_ext(dir.join("num"), "json", parse), Some(5));
+
+        // existing extensions are preserved
+        assert!(dump_type_ext(dir.join("num.txt"), "json", &6, show));
+        assert_eq!(load_type_ext(dir.join("num.txt"), "json", parse), Some(6));
+        assert!(!dir.join("num.txt.json").exists());
+
+        // but replaced by the toml wrappers
+        assert!(dump_type(dir.join("num.txt"), &7, show));
+        assert_eq!(fs::read_to_string(dir.join("num.toml")).unwrap(), "7");
+        assert_eq!(load_type(dir.join("num.txt"), parse), Some(7));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn load_or_init_only_writes_missing() {
+        let dir = test_dir("init");
+        let path = dir.join("conf/num.toml");
+        let parse = |s: &str| s.parse::<i32>();
+        let show = |x: &i32| Ok::<_, std::fmt::Error>(x.to_string());
+
+        assert_eq!(load_or_init(&path, parse, show), 0);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "0");
+
+        fs::write(&path, "7").unwrap();
+        assert_eq!(load_or_init(&path, parse, show), 7);
+
+        // corrupt files are left alone
+        fs::write(&path, "seven").unwrap();
+        assert_eq!(load_or_init(&path, parse, show), 0);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "seven");
+
+        // the extension is replaced, as with load_type
+        assert_eq!(load_or_init(dir.join("conf/other.txt"), parse, show), 0);
+        assert!(dir.join("conf/other.toml").exists());
+        assert!(!dir.join("conf/other.txt").exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn lines_strip_crlf() {
+        let input: &[u8] = b"a\r\nb\nc\r\n\r\nd";
+        let mut lines = vec![];
+        map_reader_lines::<()>(input, None, InvalidMode::Fail, |l| {
+            lines.push(l);
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(lines, ["a", "b", "c", "", "d"]);
+    }
+
+    #[test]
+    fn numbered_lines_count_skipped() {
+        let input: &[u8] = b"a\nbad\xff\nc";
+        let mut lines = vec![];
+        let result = map_reader_lines_numbered(input, None, InvalidMode::Skip, |n, l| {
+            if l == "c" {
+                return Err(format!("line {n}: unexpected {l}"));
             }
+            lines.push((n, l));
+            Ok(())
+        });
+        assert_eq!(lines, [(1, "a".to_string())]);
+        assert!(matches!(result, Err(MapReaderError::Custom(e)) if e == "line 3: unexpected c"));
+    }
+
+    #[test]
+    fn chunks_respect_max() {
+        let input: &[u8] = b"a,b,c,d";
+        let mut seen = vec![];
+        let result = map_chunks::<()>(read_to_chunks(input, ','), Some(2), InvalidMode::Fail, |c| {
+            seen.push(c);
+            Ok(())
+        });
+        assert!(matches!(result, Err(MapReaderError::ChunkError(2))));
+        assert_eq!(seen, ["a", "b"]);
+    }
+
+    #[test]
+    fn invalid_utf8_modes() {
+        let input: &[u8] = b"ok\nbad\xff\nend";
+        let run = |mode| {
+            let mut seen = vec![];
+            let result = map_reader_lines::<()>(input, None, mode, |l| {
+                seen.push(l);
+                Ok(())
+            });
+            (result.is_ok(), seen)
+        };
+
+        assert_eq!(run(InvalidMode::Skip), (true, vec!["ok".into(), "end".into()]));
+
+        let stats = |mode| map_reader_lines::<()>(input, None, mode, |_| Ok(())).unwrap();
+        assert_eq!(stats(InvalidMode::Skip), ReadStats { processed: 2, skipped: 1 });
+        assert_eq!(stats(InvalidMode::Lossy), ReadStats { processed: 3, skipped: 0 });
+        assert_eq!(run(InvalidMode::Fail), (false, vec!["ok".into()]));
+        assert_eq!(
+            run(InvalidMode::Lossy),
+            (true, vec!["ok".into(), "bad\u{FFFD}".into(), "end".into()])
+        );
+    }
+
+    #[test]
+    fn capped_reads() {
+        let dir = test_dir("capped");
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("data.toml");
+        fs::write(&path, "12345").unwrap();
+
+        assert_eq!(read_to_string_capped(&path, 5).unwrap(), "12345");
+        assert_eq!(
+            read_to_string_capped(&path, 4).unwrap_err().kind(),
+            io::ErrorKind::FileTooLarge
+        );
+        let parse = |s: &str| s.parse::<i32>();
+        assert_eq!(load_type_capped(&path, "toml", 5, parse), Some(12345));
+        assert_eq!(load_type_capped(&path, "toml", 4, parse), None);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn append_str_appends() {
+        let dir = test_dir("append");
+        let path = dir.join("log/out.ndjson");
+
+        append_str(&path, "{}\n").unwrap();
+        append_str(&path, "[]\n").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "{}\n[]\n");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn decode_text_strips_bom() {
+        let toml = "a = 1\n";
+
+        let mut utf8 = vec![0xEF, 0xBB, 0xBF];
+        utf8.extend_from_slice(toml.as_bytes());
+        assert_eq!(decode_text(utf8).unwrap(), toml);
+
+        let mut le = vec![0xFF, 0xFE];
+        le.extend(toml.encode_utf16().flat_map(u16::to_le_bytes));
+        assert_eq!(decode_text(le).unwrap(), toml);
+
+        let mut be = vec![0xFE, 0xFF];
+        be.extend(toml.encode_utf16().flat_map(u16::to_be_bytes));
+        assert_eq!(decode_text(be).unwrap(), toml);
+
+        assert_eq!(decode_text(toml.into()).unwrap(), toml);
+        assert!(decode_text(vec![0xFF, 0xFE, 0x61]).is_err());
+    }
+
+    #[test]
+    fn load_type_skips_bom() {
+        let dir = test_dir("bom");
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("num.toml");
+        fs::write(&path, b"\xEF\xBB\xBF42").unwrap();
+
+        assert_eq!(load_type(&path, |s: &str| s.parse::<i32>()), Some(42));
+        assert_eq!(read_to_string_no_bom(&path).unwrap(), "42");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn dump_type_backup_keeps_previous() {
+        let dir = test_dir("backup");
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("num.toml");
+        let show = |x: &i32| Ok::<_, std::fmt::Error>(x.to_string());
+
+        assert!(dump_type_backup(&path, "toml", &1, show));
+        assert!(!dir.join("num.toml.bak").exists());
+
+        assert!(dump_type_backup(&path, "toml", &2, show));
+        assert!(dump_type_backup(&path, "toml", &3, show));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "3");
+        assert_eq!(fs::read_to_string(dir.join("num.toml.bak")).unwrap(), "2");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(all(feature = "toml", feature = "json"))]
+    #[test]
+    fn serde_round_trips() {
+        use std::collections::BTreeMap;
+        let dir = test_dir("serde");
+        fs::create_dir_all(&dir).unwrap();
+        let map = BTreeMap::from([("a".to_string(), 1u32), ("b".to_string(), 2)]);
+
+        assert!(save_toml(dir.join("map"), &map));
+        assert_eq!(fs::read_to_string(dir.join("map.toml")).unwrap(), "a = 1\nb = 2\n");
+        assert_eq!(load_toml::<BTreeMap<String, u32>>(dir.join("map")), Some(map.clone()));
+
+        assert!(save_json(dir.join("map"), &map));
+        assert_eq!(load_json::<BTreeMap<String, u32>>(dir.join("map.json")), Some(map));
+        assert_eq!(load_json::<Vec<u32>>(dir.join("map")), None);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn hash_file_matches_hash_str() {
+        let dir = test_dir("hash");
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("f");
+
+        // large enough to span several reads
+        let content = "line of text\n".repeat(4096);
+        fs::write(&path, &content).unwrap();
+        assert_eq!(hash_file(&path), Some(hash_str(&content)));
+        assert_ne!(hash_str("a"), hash_str("b"));
+        // FNV-1a test vector
+        assert_eq!(hash_str("a"), 0xaf63dc4c8601ec8c);
+        assert_eq!(hash_file(dir.join("missing")), None);
+
+        #[cfg(feature = "sha2")]
+        {
+            fs::write(&path, "abc").unwrap();
+            let digest = sha256_file(&path).unwrap();
+            assert_eq!(digest[..4], [0xba, 0x78, 0x16, 0xbf]);
         }
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn dump_type_if_changed_skips_identical() {
+        let dir = test_dir("if_changed");
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("n.txt");
+        let to_string = |n: &u32| Ok::<_, std::fmt::Error>(n.to_string());
+
+        assert_eq!(dump_type_if_changed(&path, "txt", &1, to_string), DumpResult::Written);
+        let mtime = fs::metadata(&path).unwrap().modified().unwrap();
+        assert_eq!(dump_type_if_changed(&path, "txt", &1, to_string), DumpResult::Unchanged);
+        assert_eq!(fs::metadata(&path).unwrap().modified().unwrap(), mtime);
+        assert_eq!(dump_type_if_changed(&path, "txt", &2, to_string), DumpResult::Written);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "2");
+
+        let failing = |_: &u32| Err(std::fmt::Error);
+        assert_eq!(dump_type_if_changed(&path, "txt", &3, failing), DumpResult::Failed);
+        assert_eq!(
+            dump_type_if_changed(dir.join("missing/n"), "txt", &1, to_string),
+            DumpResult::Failed
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
     }
-    Ok(())
 }
@@ -4,9 +4,8 @@
 use std::{
     borrow::Cow,
     fmt::Display,
-    io::{Write, stderr, stdout},
+    io::{IsTerminal, Write, stderr, stdout},
     sync::Mutex,
-    u8,
 };
 
 #[derive(Clone, Copy, Debug)]
@@ -21,6 +20,59 @@ pub enum BogLevel {
     CUSTOM(&'static str),
 }
 
+impl BogLevel {
+    /// The builtin levels, from highest to lowest priority (excludes ALL and CUSTOM)
+    pub const fn all() -> &'static [BogLevel] {
+        &[
+            BogLevel::NOTE,
+            BogLevel::ERROR,
+            BogLevel::WARN,
+            BogLevel::INFO,
+            BogLevel::DEBUG,
+            BogLevel::DNOTE,
+        ]
+    }
+
+    /// The priority used by [`BogFmter::priority`] unless a formatter overrides it
+    pub const fn default_priority(self) -> u8 {
+        match self {
+            BogLevel::NOTE => 120,
+            BogLevel::ERROR => 100,
+            BogLevel::WARN => 80,
+            BogLevel::INFO => 60,
+            BogLevel::DEBUG => 40,
+            BogLevel::DNOTE => 20,
+            BogLevel::ALL => 0, // don't change
+            BogLevel::CUSTOM(_) => 120,
+        }
+    }
+}
+
+impl From<log::Level> for BogLevel {
+    fn from(level: log::Level) -> Self {
+        match level {
+            log::Level::Error => BogLevel::ERROR,
+            log::Level::Warn => BogLevel::WARN,
+            log::Level::Info => BogLevel::INFO,
+            log::Level::Debug => BogLevel::DEBUG,
+            log::Level::Trace => BogLevel::DNOTE,
+        }
+    }
+}
+
+/// NOTE and CUSTOM map to Info, ALL to Trace
+impl From<BogLevel> for log::Level {
+    fn from(level: BogLevel) -> Self {
+        match level {
+            BogLevel::ERROR => log::Level::Error,
+            BogLevel::WARN => log::Level::Warn,
+            BogLevel::NOTE | BogLevel::INFO | BogLevel::CUSTOM(_) => log::Level::Info,
+            BogLevel::DEBUG => log::Level::Debug,
+            BogLevel::DNOTE | BogLevel::ALL => log::Level::Trace,
+        }
+    }
+}
+
 pub trait BogFmter {
     fn begin_tag(&self, level: BogLevel) -> String;
     fn end_tag(&self) -> &'static str {
@@ -34,6 +86,18 @@ pub trait BogFmter {
         }
     }
 
+    /// Append key/value fields after the formatted message, dimmed by default
+    fn push_fields(&self, s: &mut String, fields: &[(&str, &str)]) {
+        s.push_str("\x1b[2m");
+        for (key, value) in fields {
+            s.push(' ');
+            s.push_str(key);
+            s.push('=');
+            s.push_str(value);
+        }
+        s.push_str("\x1b[0m");
+    }
+
     fn format(&self, level: BogLevel, tag: &str, msg: &str) -> String {
         let mut s = self.begin_tag(level);
         self.push_tag(&mut s, tag);
@@ -47,17 +111,31 @@ pub trait BogFmter {
         s
     }
 
-    fn priority(&self, level: &BogLevel) -> u8 {
-        match level {
-            BogLevel::NOTE => 120,
-            BogLevel::ERROR => 100,
-            BogLevel::WARN => 80,
-            BogLevel::INFO => 60,
-            BogLevel::DEBUG => 40,
-            BogLevel::DNOTE => 20,
-            BogLevel::ALL => 0, // don't change
-            BogLevel::CUSTOM(_) => 120,
+    /// [`BogFmter::format`], padding the level and tag to width columns (see [`Bogger::set_tag_width`]).
+    /// Formatters which override format should override this too, e.g. to ignore width.
+    fn format_aligned(&self, level: BogLevel, tag: &str, msg: &str, width: usize) -> String {
+        if width == 0 {
+            return self.format(level, tag, msg);
         }
+
+        let mut s = self.begin_tag(level);
+        self.push_tag(&mut s, tag);
+        s.push_str(self.end_tag());
+
+        // ANSI escape codes don't count towards the width
+        let padding = width.saturating_sub(visible_width(&s));
+        s.extend(std::iter::repeat_n(' ', padding));
+
+        if !msg.is_empty() {
+            s.push(' ');
+            s.push_str(msg);
+        }
+
+        s
+    }
+
+    fn priority(&self, level: &BogLevel) -> u8 {
+        level.default_priority()
     }
 }
 
@@ -71,39 +149,92 @@ pub struct GLOBAL_BOGGER_STRUCT {
     downcast_to: (u8, BogLevel),
     pub prefix: String,
     pub suffix: String,
-    pub tag_override: Option<String>
+    pub tag_override: Option<String>,
+    tag_width: usize,
+    /// Written as `[label] ` at the start of each line, before any color
+    line_label: Option<String>,
+    /// The writer is flushed after messages at or above this level
+    flush_on: (u8, BogLevel),
+    /// Messages at or above this level go to the second writer instead
+    split: Option<((u8, BogLevel), Box<dyn Write + Send + Sync>)>,
+    #[cfg(feature = "indicatif")]
+    progress: Option<indicatif::ProgressBar>,
 }
 
 impl GLOBAL_BOGGER_STRUCT {
-    fn bog(&mut self, mut level: BogLevel, tag: &str, msg: &str) {
+    fn bog(&mut self, level: BogLevel, tag: &str, msg: &str) -> Option<String> {
+        self.bog_fields(level, tag, msg, &[])
+    }
+
+    /// Returns the written line (without the trailing newline), or None if it was filtered
+    fn bog_fields(
+        &mut self,
+        mut level: BogLevel,
+        tag: &str,
+        msg: &str,
+        fields: &[(&str, &str)],
+    ) -> Option<String> {
         // Determine priority
-        let pri = self.formatter.priority(&level);
+        let mut pri = self.formatter.priority(&level);
         if pri < self.min_level.0 {
-            return;
+            return None;
         }
         if pri > self.downcast_to.0 {
-            level = self.downcast_to.1;
+            (pri, level) = self.downcast_to;
         }
 
         // Determine effective tag
         let effective_tag = self.tag_override.as_deref().unwrap_or(tag);
 
         // Format message with prefix and suffix
-        let mut formatted = if !self.prefix.is_empty() {
+        let msg: Cow<str> = if !self.prefix.is_empty() {
             let mut prefixed_msg = self.prefix.clone();
-            prefixed_msg.push_str(&msg);
-            self.formatter.format(level, effective_tag, &prefixed_msg)
+            prefixed_msg.push_str(msg);
+            prefixed_msg.into()
         } else {
-            self.formatter.format(level, effective_tag, msg)
+            msg.into()
         };
 
+        let mut formatted =
+            self.formatter
+                .format_aligned(level, effective_tag, &msg, self.tag_width);
+
+        if !fields.is_empty() {
+            self.formatter.push_fields(&mut formatted, fields);
+        }
         if !self.suffix.is_empty() {
             formatted.push_str(&self.suffix);
         }
+        if let Some(label) = &self.line_label {
+            let label = format!("[{label}] ");
+            formatted = label.clone() + &formatted.replace('\n', &format!("\n{label}"));
+        }
         formatted.push('\n');
 
         // Write to writer
-        let _ = self.writer.write_all(formatted.as_bytes());
+        let writer = match &mut self.split {
+            Some((at, split_writer)) if pri >= at.0 => split_writer,
+            _ => &mut self.writer,
+        };
+        let flush = pri >= self.flush_on.0;
+        let bytes = formatted.as_bytes();
+        let mut write = move || {
+            let _ = writer.write_all(bytes);
+            if flush {
+                let _ = writer.flush();
+            }
+        };
+        #[cfg(feature = "indicatif")]
+        match &self.progress {
+            // clear the bar while writing, then redraw it
+            Some(pb) => pb.suspend(write),
+            None => write(),
+        }
+        #[cfg(not(feature = "indicatif"))]
+        write();
+
+        formatted.pop(); // newline
+        Some(formatted)
     }
 
     fn pause(&mut self) {
@@ -122,6 +253,16 @@ impl GLOBAL_BOGGER_STRUCT {
         self.downcast_to = (self.formatter.priority(&lvl), lvl);
     }
 
+    fn flush_on(&mut self, lvl: BogLevel) {
+        self.flush_on = (self.formatter.priority(&lvl), lvl);
+    }
+
+    fn split_at(&mut self, lvl: BogLevel) {
+        if let Some((at, _)) = &mut self.split {
+            *at = (self.formatter.priority(&lvl), lvl);
+        }
+    }
+
     fn bounds(&self) -> ((u8, BogLevel), (u8, BogLevel)) {
         (self.min_level, self.downcast_to)
     }
@@ -131,20 +272,51 @@ impl GLOBAL_BOGGER_STRUCT {
         self.downcast_to = bounds.1;
     }
 
-    fn init_global(logger: Box<dyn BogFmter + Send + Sync>, write: Box<dyn Write + Send + Sync>) {
-        let bogger = GLOBAL_BOGGER_STRUCT {
+    fn new(logger: Box<dyn BogFmter + Send + Sync>, write: Box<dyn Write + Send + Sync>) -> Self {
+        let flush_on = (logger.priority(&BogLevel::ERROR), BogLevel::ERROR);
+        GLOBAL_BOGGER_STRUCT {
             formatter: logger,
             writer: write,
             downcast_to: (255, BogLevel::ERROR),
             min_level: (0, BogLevel::DEBUG),
             prefix: String::new(),
             suffix: String::new(),
-            tag_override: None
-        };
-        *GLOBAL_BOGGER.lock().unwrap() = Some(bogger);
+            tag_override: None,
+            tag_width: 0,
+            line_label: None,
+            flush_on,
+            split: None,
+            #[cfg(feature = "indicatif")]
+            progress: None,
+        }
+    }
+
+    fn init_global(logger: Box<dyn BogFmter + Send + Sync>, write: Box<dyn Write + Send + Sync>) {
+        *GLOBAL_BOGGER.lock().unwrap() = Some(Self::new(logger, write));
     }
 }
 
+/// Number of chars in s, excluding ANSI escape sequences
+fn visible_width(s: &str) -> usize {
+    let mut width = 0;
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            // CSI sequences end with a byte in @..=~
+            if chars.next() == Some('[') {
+                for c in chars.by_ref() {
+                    if ('@'..='~').contains(&c) {
+                        break;
+                    }
+                }
+            }
+        } else {
+            width += 1;
+        }
+    }
+    width
+}
+
 // since stderr has an internal lock i guess this isn't a huge deal anyways
 static GLOBAL_BOGGER: Mutex<Option<GLOBAL_BOGGER_STRUCT>> = Mutex::new(None);
 
@@ -155,6 +327,12 @@ pub fn bog(level: BogLevel, tag: &str, msg: &str) {
     Bogger::bog(level, tag, msg);
 }
 
+/// [`bog`] with key/value fields, see [`kvbog`]
+#[inline]
+pub fn bog_fields(level: BogLevel, tag: &str, msg: &str, fields: &[(&str, &str)]) {
+    Bogger::bog_fields(level, tag, msg, fields);
+}
+
 pub struct Bogger {}
 
 pub struct BogContext {
@@ -166,6 +344,7 @@ pub struct BogContext {
 }
 
 impl BogContext {
+    #[allow(clippy::new_without_default)]
     pub fn new() -> Self {
         Self {
             bounds: [None, None],
@@ -211,6 +390,7 @@ impl BogContext {
 impl Bogger {
     // don't panic
     #[inline]
+    #[allow(clippy::collapsible_if)]
     pub fn bog(level: BogLevel, tag: &str, msg: &str) {
         if let Ok(mut guard) = GLOBAL_BOGGER.lock() {
             if let Some(b) = guard.as_mut() {
@@ -220,6 +400,28 @@ impl Bogger {
     }
 
     #[inline]
+    pub fn bog_fields(level: BogLevel, tag: &str, msg: &str, fields: &[(&str, &str)]) {
+        if let Ok(mut guard) = GLOBAL_BOGGER.lock()
+            && let Some(b) = guard.as_mut()
+        {
+            b.bog_fields(level, tag, msg, fields);
+        }
+    }
+
+    /// [`Bogger::bog`], also returning the written line (without the trailing newline).
+    /// Returns None if the message was filtered out, or the bogger is uninitialized.
+    pub fn bog_fmt(level: BogLevel, tag: &str, msg: &str) -> Option<String> {
+        if let Ok(mut guard) = GLOBAL_BOGGER.lock()
+            && let Some(b) = guard.as_mut()
+        {
+            b.bog(level, tag, msg)
+        } else {
+            None
+        }
+    }
+
+    #[inline]
+    #[allow(clippy::collapsible_if)]
     pub fn filter_below(lvl: BogLevel) {
         if let Ok(mut guard) = GLOBAL_BOGGER.lock() {
             if let Some(b) = guard.as_mut() {
@@ -229,6 +431,7 @@ impl Bogger {
     }
 
     #[inline]
+    #[allow(clippy::collapsible_if)]
     pub fn downcast_above(lvl: BogLevel) {
         if let Ok(mut guard) = GLOBAL_BOGGER.lock() {
             if let Some(b) = guard.as_mut() {
@@ -238,6 +441,7 @@ impl Bogger {
     }
 
     #[inline]
+    #[allow(clippy::collapsible_if)]
     pub fn with<T>(context: BogContext, f: impl FnOnce() -> T) -> T {
         let (prev_bounds, prev_paused, prev_prefix, prev_suffix, prev_tag) = if let Ok(mut guard) = GLOBAL_BOGGER.lock() {
             if let Some(b) = guard.as_mut() {
@@ -309,6 +513,90 @@ impl Bogger {
         result
     }
 
+    /// Send output to writer while running f, restoring the previous writer afterwards (even if f panics).
+    /// Does nothing if the bogger is uninitialized.
+    pub fn with_writer<T>(writer: Box<dyn Write + Send + Sync>, f: impl FnOnce() -> T) -> T {
+        struct Restore(Option<Box<dyn Write + Send + Sync>>);
+
+        impl Drop for Restore {
+            fn drop(&mut self) {
+                if let Some(prev) = self.0.take()
+                    && let Ok(mut guard) = GLOBAL_BOGGER.lock()
+                    && let Some(b) = guard.as_mut()
+                {
+                    let _ = b.writer.flush();
+                    b.writer = prev;
+                }
+            }
+        }
+
+        let prev = if let Ok(mut guard) = GLOBAL_BOGGER.lock()
+            && let Some(b) = guard.as_mut()
+        {
+            Some(std::mem::replace(&mut b.writer, writer))
+        } else {
+            None
+        };
+        let _restore = Restore(prev);
+
+        f()
+    }
+
+    /// Send messages at or above lvl to stderr and the rest to stdout, see [`init_bogger_split`].
+    /// Does nothing if the bogger wasn't initialized with a split.
+    #[inline]
+    pub fn split_at(lvl: BogLevel) {
+        if let Ok(mut guard) = GLOBAL_BOGGER.lock()
+            && let Some(b) = guard.as_mut()
+        {
+            b.split_at(lvl);
+        }
+    }
+
+    /// Flush the writer after every message at or above lvl (ERROR by default),
+    /// so that they aren't lost in a buffer if the program then crashes or exits.
+    #[inline]
+    pub fn set_flush_on(lvl: BogLevel) {
+        if let Ok(mut guard) = GLOBAL_BOGGER.lock()
+            && let Some(b) = guard.as_mut()
+        {
+            b.flush_on(lvl);
+        }
+    }
+
+    /// Pad the formatted level and tag to width columns, so that messages align.
+    /// ANSI escape codes don't count towards the width, 0 disables padding.
+    #[inline]
+    pub fn set_tag_width(width: usize) {
+        if let Ok(mut guard) = GLOBAL_BOGGER.lock()
+            && let Some(b) = guard.as_mut()
+        {
+            b.tag_width = width;
+        }
+    }
+
+    /// Start every written line with `[label] `, e.g. to tell apart the output of processes in a pipeline.
+    /// Unlike [`GLOBAL_BOGGER_STRUCT::prefix`], this comes before the formatted level and tag.
+    #[inline]
+    pub fn set_line_label(label: Option<String>) {
+        if let Ok(mut guard) = GLOBAL_BOGGER.lock()
+            && let Some(b) = guard.as_mut()
+        {
+            b.line_label = label;
+        }
+    }
+
+    /// [`Bogger::set_line_label`] with the name of the running program (the file stem of `argv[0]`)
+    #[inline]
+    pub fn label_with_program_name() {
+        let name = std::env::args_os().next().and_then(|arg0| {
+            std::path::Path::new(&arg0)
+                .file_stem()
+                .map(|s| s.to_string_lossy().into_owned())
+        });
+        Bogger::set_line_label(name);
+    }
+
     #[inline]
     pub fn paused<T>(f: impl FnOnce() -> T) -> T {
         Bogger::pause();
@@ -318,6 +606,7 @@ impl Bogger {
     }
 
     #[inline]
+    #[allow(clippy::collapsible_if)]
     pub fn pause() {
         if let Ok(mut guard) = GLOBAL_BOGGER.lock() {
             if let Some(b) = guard.as_mut() {
@@ -326,7 +615,51 @@ impl Bogger {
         }
     }
 
+    /// Install a Ctrl-C (SIGINT, and SIGTERM/SIGHUP with ctrlc's `termination` feature) handler
+    /// which bogs an "Interrupted" NOTE, flushes the writer, then runs f.
+    ///
+    /// The handler runs on a dedicated thread rather than in signal context, so f isn't limited to async-signal-safe operations.
+    /// However, it may run while the interrupted thread is in the middle of anything (including holding locks f needs),
+    /// and the process is no longer terminated by the signal: call [`std::process::exit`] (conventionally with 130) from f to stop.
+    /// Only one handler can be installed per process, returns false (after logging) if one already was.
+    #[cfg(feature = "signal")]
+    pub fn on_interrupt(f: impl Fn() + Send + 'static) -> bool {
+        let result = ctrlc::set_handler(move || {
+            Bogger::bog(BogLevel::NOTE, "", "Interrupted");
+            if let Ok(mut guard) = GLOBAL_BOGGER.lock()
+                && let Some(b) = guard.as_mut()
+            {
+                let _ = b.writer.flush();
+            }
+            f();
+        });
+
+        match result {
+            Ok(()) => true,
+            Err(e) => {
+                Bogger::bog(
+                    BogLevel::ERROR,
+                    "",
+                    &format!("Failed to install interrupt handler: {e}"),
+                );
+                false
+            }
+        }
+    }
+
+    /// Route output around pb (see [`indicatif::ProgressBar::suspend`]) so messages don't clobber it.
+    /// For a [`indicatif::MultiProgress`], register any of its bars.
+    #[cfg(feature = "indicatif")]
+    pub fn set_progress(pb: Option<indicatif::ProgressBar>) {
+        if let Ok(mut guard) = GLOBAL_BOGGER.lock()
+            && let Some(b) = guard.as_mut()
+        {
+            b.progress = pb;
+        }
+    }
+
     #[inline]
+    #[allow(clippy::collapsible_if)]
     pub fn resume() {
         if let Ok(mut guard) = GLOBAL_BOGGER.lock() {
             if let Some(b) = guard.as_mut() {
@@ -336,40 +669,94 @@ impl Bogger {
     }
 }
 // -------- IMPL ---------
+/// How builtin levels are named by [`init_bogger_with_names`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LevelNames {
+    /// Four letters: `ERRO`, `DBUG`, `DNTE` ([`Fg`], [`BgAbbrev`])
+    Abbreviated,
+    /// `ERROR`, `DEBUG`, `DNOTE` ([`FgFull`], [`Bg`])
+    Full,
+}
+
+fn level_name(level: BogLevel, names: LevelNames) -> &'static str {
+    use LevelNames::*;
+    match (level, names) {
+        (BogLevel::NOTE, _) => "NOTE",
+        (BogLevel::ERROR, Abbreviated) => "ERRO",
+        (BogLevel::ERROR, Full) => "ERROR",
+        (BogLevel::WARN, _) => "WARN",
+        (BogLevel::INFO, _) => "INFO",
+        (BogLevel::DEBUG, Abbreviated) => "DBUG",
+        (BogLevel::DEBUG, Full) => "DEBUG",
+        (BogLevel::DNOTE, Abbreviated) => "DNTE",
+        (BogLevel::DNOTE, Full) => "DNOTE",
+        (BogLevel::ALL, _) => "", // unreachable
+        (BogLevel::CUSTOM(s), _) => s,
+    }
+}
+
+fn fg_begin_tag(level: BogLevel, names: LevelNames) -> String {
+    let code = match level {
+        BogLevel::NOTE => "34",      // blue foreground
+        BogLevel::ERROR => "31",     // red foreground
+        BogLevel::WARN => "33",      // yellow foreground
+        BogLevel::INFO => "32",      // green foreground
+        BogLevel::DEBUG => "35",     // purple/magenta foreground
+        BogLevel::DNOTE => "30",     // black foreground
+        BogLevel::ALL => "",         // unreachable
+        BogLevel::CUSTOM(_) => "34", // blue foreground
+    };
+    format!("\x1b[{code}m[{}", level_name(level, names))
+}
+
+fn bg_begin_tag(level: BogLevel, names: LevelNames) -> String {
+    let code = match level {
+        BogLevel::NOTE => "44",      // blue bg
+        BogLevel::ERROR => "41",     // red bg
+        BogLevel::WARN => "43",      // yellow bg
+        BogLevel::INFO => "42",      // green bg
+        BogLevel::DEBUG => "45",     // purple bg
+        BogLevel::DNOTE => "47",     // white bg
+        BogLevel::ALL => "",         // unreachable
+        BogLevel::CUSTOM(_) => "44", // blue bg
+    };
+    // builtin names are padded to the same width
+    let width = match (level, names) {
+        (BogLevel::CUSTOM(_), _) => 0,
+        (_, LevelNames::Abbreviated) => 4,
+        (_, LevelNames::Full) => 5,
+    };
+    let name = level_name(level, names);
+    format!("\x1b[30;{code}m{name:<width$}") // colored bg with black text (white also looks (worse))
+}
+
+/// `[ERRO]` in the level's color
 pub struct Fg {}
 impl BogFmter for Fg {
     fn begin_tag(&self, level: BogLevel) -> String {
-        let (code, level) = match level {
-            BogLevel::NOTE => ("34", "NOTE"),  // blue foreground
-            BogLevel::ERROR => ("31", "ERRO"), // red foreground
-            BogLevel::WARN => ("33", "WARN"),  // yellow foreground
-            BogLevel::INFO => ("32", "INFO"),  // green foreground
-            BogLevel::DEBUG => ("35", "DBUG"), // purple/magenta foreground
-            BogLevel::DNOTE => ("30", "DNTE"), // black foreground
-            BogLevel::ALL => ("", ""),         // unreachable
-            BogLevel::CUSTOM(s) => ("34", s),  // blue foreground
-        };
-        format!("\x1b[{code}m[{level}")
+        fg_begin_tag(level, LevelNames::Abbreviated)
     }
     fn end_tag(&self) -> &'static str {
         "]\x1b[0m"
     }
 }
 
+/// [`Fg`] with full level names: `[ERROR]`
+pub struct FgFull {}
+impl BogFmter for FgFull {
+    fn begin_tag(&self, level: BogLevel) -> String {
+        fg_begin_tag(level, LevelNames::Full)
+    }
+    fn end_tag(&self) -> &'static str {
+        Fg {}.end_tag()
+    }
+}
+
+/// ` ERROR ` on the level's color
 pub struct Bg {}
 impl BogFmter for Bg {
     fn begin_tag(&self, level: BogLevel) -> String {
-        let (code, level) = match level {
-            BogLevel::NOTE => ("44", "NOTE "),  // blue bg
-            BogLevel::ERROR => ("41", "ERROR"), // red bg
-            BogLevel::WARN => ("43", "WARN "),  // yellow bg
-            BogLevel::INFO => ("42", "INFO "),  // green bg
-            BogLevel::DEBUG => ("45", "DEBUG"), // purple bg
-            BogLevel::DNOTE => ("47", "DNOTE"), // white bg
-            BogLevel::ALL => ("", ""),          // unreachable
-            BogLevel::CUSTOM(s) => ("44", s),   // blue bg
-        };
-        format!("\x1b[30;{code}m{level}") // colored bg with black text (white also looks (worse))
+        bg_begin_tag(level, LevelNames::Full)
     }
     fn push_tag(&self, s: &mut String, tag: &str) {
         if !tag.is_empty() {
@@ -382,18 +769,209 @@ impl BogFmter for Bg {
     }
 }
 
+/// [`Bg`] with abbreviated level names: ` ERRO `
+pub struct BgAbbrev {}
+impl BogFmter for BgAbbrev {
+    fn begin_tag(&self, level: BogLevel) -> String {
+        bg_begin_tag(level, LevelNames::Abbreviated)
+    }
+    fn push_tag(&self, s: &mut String, tag: &str) {
+        Bg {}.push_tag(s, tag)
+    }
+    fn end_tag(&self) -> &'static str {
+        Bg {}.end_tag()
+    }
+}
+
+/// Machine-readable `level=error tag=404 msg="Not found"` output.
+/// Values containing spaces, quotes, `=` or control characters are quoted and escaped, an empty tag is omitted.
+pub struct Logfmt {}
+impl BogFmter for Logfmt {
+    fn begin_tag(&self, level: BogLevel) -> String {
+        let level = match level {
+            BogLevel::NOTE => "note".into(),
+            BogLevel::ERROR => "error".into(),
+            BogLevel::WARN => "warn".into(),
+            BogLevel::INFO => "info".into(),
+            BogLevel::DEBUG => "debug".into(),
+            BogLevel::DNOTE => "dnote".into(),
+            BogLevel::ALL => "all".into(),
+            BogLevel::CUSTOM(s) => s.to_lowercase(),
+        };
+        format!("level={}", logfmt_value(&level))
+    }
+    fn push_tag(&self, s: &mut String, tag: &str) {
+        if !tag.is_empty() {
+            s.push_str(" tag=");
+            s.push_str(&logfmt_value(tag));
+        }
+    }
+    fn end_tag(&self) -> &'static str {
+        ""
+    }
+    fn push_fields(&self, s: &mut String, fields: &[(&str, &str)]) {
+        for (key, value) in fields {
+            s.push(' ');
+            s.push_str(key);
+            s.push('=');
+            s.push_str(&logfmt_value(value));
+        }
+    }
+    /// Alignment would break the key=value structure, so width is ignored
+    fn format_aligned(&self, level: BogLevel, tag: &str, msg: &str, _width: usize) -> String {
+        self.format(level, tag, msg)
+    }
+    fn format(&self, level: BogLevel, tag: &str, msg: &str) -> String {
+        let mut s = self.begin_tag(level);
+        self.push_tag(&mut s, tag);
+        if !msg.is_empty() {
+            s.push_str(" msg=");
+            s.push_str(&logfmt_value(msg));
+        }
+        s
+    }
+}
+
+fn logfmt_value(value: &str) -> Cow<'_, str> {
+    if !value.is_empty()
+        && !value
+            .chars()
+            .any(|c| c == ' ' || c == '"' || c == '=' || c == '\\' || c.is_control())
+    {
+        return value.into();
+    }
+
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            _ => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted.into()
+}
+
+/// Removes ANSI escape sequences (such as colors) from the bytes written through it.
+/// Sequences may be split across writes.
+pub struct AnsiStripWriter<W: Write> {
+    inner: W,
+    state: AnsiState,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum AnsiState {
+    Text,
+    Escape,
+    Csi,
+}
+
+impl<W: Write> AnsiStripWriter<W> {
+    pub fn new(inner: W) -> Self {
+        Self {
+            inner,
+            state: AnsiState::Text,
+        }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: Write> Write for AnsiStripWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let mut text = Vec::with_capacity(buf.len());
+        for &b in buf {
+            self.state = match (self.state, b) {
+                (AnsiState::Text, 0x1b) => AnsiState::Escape,
+                (AnsiState::Text, _) => {
+                    text.push(b);
+                    AnsiState::Text
+                }
+                (AnsiState::Escape, b'[') => AnsiState::Csi,
+                // two byte sequence
+                (AnsiState::Escape, _) => AnsiState::Text,
+                // CSI sequences end with a byte in @..=~
+                (AnsiState::Csi, 0x40..=0x7e) => AnsiState::Text,
+                (AnsiState::Csi, _) => AnsiState::Csi,
+            };
+        }
+        self.inner.write_all(&text)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+fn strip_unless_terminal<W>(stream: W) -> Box<dyn Write + Send + Sync>
+where
+    W: Write + IsTerminal + Send + Sync + 'static,
+{
+    if stream.is_terminal() {
+        Box::new(stream)
+    } else {
+        Box::new(AnsiStripWriter::new(stream))
+    }
+}
+
 // ----------- PUBLIC -------------
+/// Color is stripped (see [`AnsiStripWriter`]) if the output stream isn't a terminal.
+/// Levels are abbreviated with [`Fg`], and in full with [`Bg`].
 pub fn init_bogger(fg: bool, output_stderr: bool) {
+    let names = if fg {
+        LevelNames::Abbreviated
+    } else {
+        LevelNames::Full
+    };
+    init_bogger_with_names(fg, output_stderr, names);
+}
+
+/// [`init_bogger`], choosing how levels are named
+pub fn init_bogger_with_names(fg: bool, output_stderr: bool, names: LevelNames) {
+    let writer = if output_stderr {
+        strip_unless_terminal(stderr())
+    } else {
+        strip_unless_terminal(stdout())
+    };
+
+    let formatter: Box<dyn BogFmter + Send + Sync> = match (fg, names) {
+        (true, LevelNames::Abbreviated) => Box::new(Fg {}),
+        (true, LevelNames::Full) => Box::new(FgFull {}),
+        (false, LevelNames::Abbreviated) => Box::new(BgAbbrev {}),
+        (false, LevelNames::Full) => Box::new(Bg {}),
+    };
+    GLOBAL_BOGGER_STRUCT::init_global(formatter, writer);
+}
+
+/// Initialize the bogger with the [`Logfmt`] formatter
+pub fn init_bogger_logfmt(output_stderr: bool) {
     let writer: Box<dyn Write + Send + Sync> = if output_stderr {
         Box::new(stderr())
     } else {
         Box::new(stdout())
     };
+    GLOBAL_BOGGER_STRUCT::init_global(Box::new(Logfmt {}), writer);
+}
 
-    if fg {
-        GLOBAL_BOGGER_STRUCT::init_global(Box::new(Fg {}), writer);
-    } else {
-        GLOBAL_BOGGER_STRUCT::init_global(Box::new(Bg {}), writer);
+/// Initialize the bogger with messages at or above WARN priority going to stderr, and everything else to stdout.
+/// This includes NOTE and CUSTOM, which rank above ERROR, so only INFO and below go to stdout.
+/// This keeps a tool's output pipeable while diagnostics remain visible.
+/// The split point can be changed with [`Bogger::split_at`].
+pub fn init_bogger_split(fg: bool) {
+    init_bogger(fg, false);
+    if let Ok(mut guard) = GLOBAL_BOGGER.lock()
+        && let Some(b) = guard.as_mut()
+    {
+        let at = (b.formatter.priority(&BogLevel::WARN), BogLevel::WARN);
+        b.split = Some((at, strip_unless_terminal(stderr())));
     }
 }
 
@@ -418,6 +996,108 @@ pub fn init_filter(verbosity: u8) {
     }
 }
 
+/// [`init_filter`] with verbosity `default + verbose - quiet`, clamped at 0.
+pub fn init_filter_ex(default: u8, verbose: u8, quiet: u8) {
+    init_filter(default.saturating_add(verbose).saturating_sub(quiet))
+}
+
+/// `-v/--verbose` and `-q/--quiet` counters for flattening into a clap parser.
+///
+/// ```no_run
+/// use clap::Parser;
+/// use cli_boilerplate_automation::bog::BogVerbosity;
+///
+/// #[derive(Parser)]
+/// struct Cli {
+///     #[command(flatten)]
+///     verbosity: BogVerbosity,
+/// }
+///
+/// Cli::parse().verbosity.apply();
+/// ```
+#[cfg(feature = "clap")]
+#[derive(clap::Args, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BogVerbosity {
+    /// Increase verbosity
+    #[arg(short, long, action = clap::ArgAction::Count, global = true)]
+    pub verbose: u8,
+    /// Decrease verbosity
+    #[arg(short, long, action = clap::ArgAction::Count, global = true, conflicts_with = "verbose")]
+    pub quiet: u8,
+}
+
+#[cfg(feature = "clap")]
+impl BogVerbosity {
+    /// Set the filter, showing INFO and above by default
+    pub fn apply(&self) {
+        self.apply_with_default(2)
+    }
+
+    /// Set the filter, starting from the given [`init_filter`] verbosity
+    pub fn apply_with_default(&self, default: u8) {
+        init_filter_ex(default, self.verbose, self.quiet)
+    }
+}
+
+/// [`log::Log`] backend which forwards records to the global bogger, tagged with their target.
+/// Install with [`init_log_bridge`].
+#[cfg(feature = "log-bridge")]
+pub struct BogLog;
+
+#[cfg(feature = "log-bridge")]
+impl log::Log for BogLog {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        if let Ok(guard) = GLOBAL_BOGGER.lock()
+            && let Some(b) = guard.as_ref()
+        {
+            b.formatter.priority(&metadata.level().into()) >= b.min_level.0
+        } else {
+            false
+        }
+    }
+
+    fn log(&self, record: &log::Record) {
+        if self.enabled(record.metadata()) {
+            bog(
+                record.level().into(),
+                record.target(),
+                &record.args().to_string(),
+            );
+        }
+    }
+
+    fn flush(&self) {
+        if let Ok(mut guard) = GLOBAL_BOGGER.lock()
+            && let Some(b) = guard.as_mut()
+        {
+            let _ = b.writer.flush();
+        }
+    }
+}
+
+/// Install [`BogLog`] as the [`log`] backend, so that `log::info!` and co. are shown by the bogger.
+/// Filtering is left to the bogger (see [`init_filter`]).
+/// Returns false (after logging) if a backend was already installed.
+#[cfg(feature = "log-bridge")]
+pub fn init_log_bridge() -> bool {
+    static LOGGER: BogLog = BogLog;
+
+    match log::set_logger(&LOGGER) {
+        Ok(()) => {
+            log::set_max_level(log::LevelFilter::Trace);
+            true
+        }
+        Err(e) => {
+            bog(
+                BogLevel::ERROR,
+                "",
+                &format!("Failed to install log bridge: {e}"),
+            );
+            false
+        }
+    }
+}
+
 // ----------- MACROS ------------------
 #[macro_export]
 macro_rules! ibog {
@@ -547,6 +1227,34 @@ macro_rules! cbog {
     }};
 }
 
+/// Bog at the given level with key/value fields, which are rendered after the message (see [`BogFmter::push_fields`]).
+/// Values can be anything implementing Display.
+///
+/// ```rust
+/// use cli_boilerplate_automation::kvbog;
+///
+/// let (n, p) = (3, "a.txt");
+/// kvbog!(ERROR, "upload"; "retries" => n, "path" => p; "Failed after {} retries", n);
+/// kvbog!(INFO; "path" => p; "Done");
+/// ```
+#[macro_export]
+macro_rules! kvbog {
+    ($level:ident, $($harg:expr),+ ; $($key:expr => $value:expr),* ; $($arg:expr),+) => {{
+        let values = [$(format!("{}", $value)),*];
+        let keys: [&str; _] = [$($key),*];
+        let fields: Vec<(&str, &str)> = keys.iter().copied().zip(values.iter().map(String::as_str)).collect();
+        $crate::bog::bog_fields(
+            $crate::bog::BogLevel::$level,
+            &format!($($harg),+),
+            &format!($($arg),+),
+            &fields,
+        );
+    }};
+    ($level:ident ; $($key:expr => $value:expr),* ; $($arg:expr),+) => {{
+        $crate::kvbog!($level, ""; $($key => $value),* ; $($arg),+)
+    }};
+}
+
 // ----------- RESULT -----------------
 
 /// # Example
@@ -647,8 +1355,16 @@ pub impl<T> Option<T> {
 mod test {
     use super::*;
 
+    /// Serializes tests which (re)initialize or redirect the global bogger
+    fn lock_global() -> std::sync::MutexGuard<'static, ()> {
+        static LOCK: Mutex<()> = Mutex::new(());
+        LOCK.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
     #[test]
+    #[allow(clippy::approx_constant)]
     fn show_fg_bogger() {
+        let _lock = lock_global();
         init_bogger(true, false);
         // DEBUG messages
         dbog!("DEBUG message: {}", 3.14159);
@@ -679,7 +1395,9 @@ mod test {
     }
 
     #[test]
+    #[allow(clippy::approx_constant)]
     fn show_bg_bogger() {
+        let _lock = lock_global();
         init_bogger(false, true);
         // DEBUG messages
         dbog!("DEBUG message: {}", 3.14159);
@@ -711,6 +1429,7 @@ mod test {
 
     #[test]
     fn min_level_and_downcast_combined() {
+        let _lock = lock_global();
         init_bogger(true, false);
 
         // drop DEBUG/INFO entirely
@@ -722,4 +1441,348 @@ mod test {
         ibog!("info normal");
         ebog!("error shown as warn");
     }
+
+    #[cfg(feature = "clap")]
+    #[test]
+    fn verbosity_flags() {
+        use clap::Parser;
+
+        #[derive(Parser)]
+        struct Cli {
+            #[command(flatten)]
+            verbosity: BogVerbosity,
+        }
+
+        let v = Cli::parse_from(["cli", "-vv"]).verbosity;
+        assert_eq!((v.verbose, v.quiet), (2, 0));
+        let v = Cli::parse_from(["cli", "--quiet"]).verbosity;
+        assert_eq!((v.verbose, v.quiet), (0, 1));
+        assert!(Cli::try_parse_from(["cli", "-v", "-q"]).is_err());
+    }
+
+    #[cfg(feature = "indicatif")]
+    #[test]
+    fn bog_around_progress() {
+        let _lock = lock_global();
+        init_bogger(true, true);
+        let pb = indicatif::ProgressBar::hidden();
+        Bogger::set_progress(Some(pb.clone()));
+        ibog!("suspending {}", pb.position());
+        Bogger::set_progress(None);
+    }
+
+    /// Shared buffer for asserting on bogged output
+    #[derive(Clone, Default)]
+    struct Capture(std::sync::Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Capture {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Capture {
+        fn take(&self) -> String {
+            String::from_utf8(std::mem::take(&mut *self.0.lock().unwrap())).unwrap()
+        }
+    }
+
+    /// Fg with a configurable priority for CUSTOM("low")
+    struct LowCustom(u8);
+
+    impl BogFmter for LowCustom {
+        fn begin_tag(&self, level: BogLevel) -> String {
+            Fg {}.begin_tag(level)
+        }
+        fn priority(&self, level: &BogLevel) -> u8 {
+            match level {
+                BogLevel::CUSTOM("low") => self.0,
+                _ => Fg {}.priority(level),
+            }
+        }
+    }
+
+    #[test]
+    fn custom_levels_through_pipeline() {
+        // a local bogger, so parallel tests touching the global don't interfere
+        let capture = Capture::default();
+        let mut bogger =
+            GLOBAL_BOGGER_STRUCT::new(Box::new(LowCustom(30)), Box::new(capture.clone()));
+        bogger.filter_below(BogLevel::INFO);
+
+        bogger.bog(BogLevel::CUSTOM("HIGH"), "", "kept");
+        bogger.bog(BogLevel::CUSTOM("low"), "", "dropped");
+        bogger.bog(BogLevel::DEBUG, "", "dropped");
+        let out = capture.take();
+        assert!(out.contains("HIGH") && out.contains("kept"));
+        assert!(!out.contains("low") && !out.contains("dropped"));
+
+        // raising the custom priority above the filter lets it through
+        let mut bogger =
+            GLOBAL_BOGGER_STRUCT::new(Box::new(LowCustom(70)), Box::new(capture.clone()));
+        bogger.filter_below(BogLevel::INFO);
+        bogger.bog(BogLevel::CUSTOM("low"), "", "kept");
+        assert!(capture.take().contains("kept"));
+    }
+
+    #[test]
+    fn custom_levels_are_downcast() {
+        let capture = Capture::default();
+        let mut bogger =
+            GLOBAL_BOGGER_STRUCT::new(Box::new(LowCustom(30)), Box::new(capture.clone()));
+        bogger.filter_below(BogLevel::INFO);
+        bogger.downcast_above(BogLevel::WARN);
+
+        // CUSTOM(120) is above WARN, so it survives the filter but is shown as WARN
+        bogger.bog(BogLevel::CUSTOM("HIGH"), "", "msg");
+        let out = capture.take();
+        assert!(out.starts_with(&Fg {}.begin_tag(BogLevel::WARN)));
+        assert!(!out.contains("HIGH") && out.contains("msg"));
+
+        // below the downcast bound, levels are left as is
+        bogger.bog(BogLevel::INFO, "", "msg");
+        assert!(capture.take().starts_with(&Fg {}.begin_tag(BogLevel::INFO)));
+
+        bogger.bog(BogLevel::CUSTOM("low"), "", "msg");
+        assert_eq!(capture.take(), "");
+    }
+
+    #[test]
+    fn with_writer_restores() {
+        let _lock = lock_global();
+        init_bogger(true, false);
+        let outer = Capture::default();
+        let inner = Capture::default();
+
+        Bogger::with_writer(Box::new(outer.clone()), || {
+            Bogger::with_writer(Box::new(inner.clone()), || ebog!("inner"));
+            ebog!("outer");
+
+            // restored when unwinding
+            let _ = std::panic::catch_unwind(|| {
+                Bogger::with_writer(Box::new(inner.clone()), || panic!("in with_writer"))
+            });
+            ebog!("after panic");
+        });
+
+        assert!(inner.take().contains("inner"));
+        let out = outer.take();
+        assert!(!out.contains("inner"));
+        assert!(out.contains("outer") && out.contains("after panic"));
+    }
+
+    #[test]
+    fn log_level_conversions() {
+        for level in [
+            log::Level::Error,
+            log::Level::Warn,
+            log::Level::Info,
+            log::Level::Debug,
+            log::Level::Trace,
+        ] {
+            assert_eq!(log::Level::from(BogLevel::from(level)), level);
+        }
+        assert_eq!(log::Level::from(BogLevel::CUSTOM("x")), log::Level::Info);
+    }
+
+    #[cfg(feature = "log-bridge")]
+    #[test]
+    fn log_bridge() {
+        let _lock = lock_global();
+        init_bogger(true, false);
+        assert!(init_log_bridge());
+        assert!(!init_log_bridge());
+
+        let capture = Capture::default();
+        Bogger::with_writer(Box::new(capture.clone()), || {
+            log::warn!(target: "lib", "bridged");
+            Bogger::filter_below(BogLevel::ERROR);
+            log::warn!("filtered");
+            Bogger::filter_below(BogLevel::DEBUG);
+        });
+
+        let out = capture.take();
+        assert!(out.starts_with(&Fg {}.begin_tag(BogLevel::WARN)));
+        assert!(out.contains("lib") && out.contains("bridged"));
+        assert!(!out.contains("filtered"));
+    }
+
+    #[test]
+    fn tag_width_aligns_messages() {
+        assert_eq!(visible_width("\x1b[30;41mERROR| tag \x1b[0m"), 11);
+
+        let capture = Capture::default();
+        let mut bogger = GLOBAL_BOGGER_STRUCT::new(Box::new(Fg {}), Box::new(capture.clone()));
+        bogger.tag_width = 16;
+        bogger.bog(BogLevel::INFO, "", "a");
+        bogger.bog(BogLevel::WARN, "longer", "b");
+        bogger.bog(BogLevel::ERROR, "much longer than width", "c");
+
+        let out = capture.take();
+        let lines: Vec<_> = out.lines().collect();
+        assert_eq!(visible_width(lines[0]), 18);
+        assert_eq!(visible_width(lines[1]), 18);
+        assert!(lines[0].ends_with("         a"));
+        assert!(lines[2].ends_with("width]\x1b[0m c"));
+
+        let capture = Capture::default();
+        let mut bogger = GLOBAL_BOGGER_STRUCT::new(Box::new(Logfmt {}), Box::new(capture.clone()));
+        bogger.tag_width = 16;
+        bogger.bog(BogLevel::INFO, "x", "some message");
+        assert_eq!(capture.take(), "level=info tag=x msg=\"some message\"\n");
+    }
+
+    #[test]
+    fn split_by_level() {
+        let (low, high) = (Capture::default(), Capture::default());
+        let mut bogger = GLOBAL_BOGGER_STRUCT::new(Box::new(Fg {}), Box::new(low.clone()));
+        bogger.split = Some(((0, BogLevel::ALL), Box::new(high.clone())));
+        bogger.split_at(BogLevel::WARN);
+
+        bogger.bog(BogLevel::INFO, "", "info");
+        bogger.bog(BogLevel::WARN, "", "warn");
+        bogger.bog(BogLevel::ERROR, "", "error");
+        let (out, err) = (low.take(), high.take());
+        assert!(out.contains("info") && !out.contains("warn") && !out.contains("error"));
+        assert!(!err.contains("info") && err.contains("warn") && err.contains("error"));
+
+        // NOTE and CUSTOM have the highest priority
+        bogger.bog(BogLevel::NOTE, "", "note");
+        bogger.bog(BogLevel::CUSTOM("CUSTOM"), "", "custom");
+        assert_eq!(low.take(), "");
+        let err = high.take();
+        assert!(err.contains("note") && err.contains("custom"));
+
+        // the split applies to the downcast level
+        bogger.split_at(BogLevel::ERROR);
+        bogger.downcast_above(BogLevel::WARN);
+        bogger.bog(BogLevel::ERROR, "", "error");
+        assert!(low.take().contains("error"));
+        assert_eq!(high.take(), "");
+    }
+
+    #[test]
+    fn logfmt_output() {
+        let fmt = Logfmt {};
+        assert_eq!(
+            fmt.format(BogLevel::ERROR, "404", "Not found"),
+            r#"level=error tag=404 msg="Not found""#
+        );
+        assert_eq!(
+            fmt.format(BogLevel::INFO, "", "done"),
+            "level=info msg=done"
+        );
+        assert_eq!(fmt.format(BogLevel::CUSTOM("Audit"), "", ""), "level=audit");
+        assert_eq!(
+            fmt.format(BogLevel::WARN, "a b", "say \"hi\"\\n\nx=1"),
+            r#"level=warn tag="a b" msg="say \"hi\"\\n\nx=1""#
+        );
+    }
+
+    #[test]
+    fn ansi_strip_writer() {
+        let capture = Capture::default();
+        let mut writer = AnsiStripWriter::new(capture.clone());
+        let colored = Fg {}.format(BogLevel::WARN, "tag", "msg \x1b[1mbold\x1b[0m");
+        // split inside escape sequences
+        for chunk in colored.as_bytes().chunks(3) {
+            writer.write_all(chunk).unwrap();
+        }
+        assert_eq!(capture.take(), "[WARN: tag] msg bold");
+    }
+
+    #[test]
+    fn fields_after_message() {
+        let capture = Capture::default();
+        let mut bogger = GLOBAL_BOGGER_STRUCT::new(Box::new(Logfmt {}), Box::new(capture.clone()));
+        bogger.suffix = " end".into();
+        let fields = [("retries", "3"), ("path", "a b")];
+        bogger.bog_fields(BogLevel::ERROR, "", "failed", &fields);
+        assert_eq!(
+            capture.take(),
+            "level=error msg=failed retries=3 path=\"a b\" end\n"
+        );
+
+        let mut s = Fg {}.format(BogLevel::INFO, "", "msg");
+        Fg {}.push_fields(&mut s, &[("k", "v")]);
+        assert!(s.ends_with("msg\x1b[2m k=v\x1b[0m"));
+    }
+
+    #[test]
+    fn flush_on_level() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        #[derive(Clone, Default)]
+        struct Flushes(std::sync::Arc<AtomicUsize>);
+        impl Write for Flushes {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                Ok(buf.len())
+            }
+            fn flush(&mut self) -> std::io::Result<()> {
+                self.0.fetch_add(1, Ordering::Relaxed);
+                Ok(())
+            }
+        }
+
+        let flushes = Flushes::default();
+        let mut bogger = GLOBAL_BOGGER_STRUCT::new(Box::new(Fg {}), Box::new(flushes.clone()));
+        bogger.bog(BogLevel::WARN, "", "");
+        bogger.bog(BogLevel::ERROR, "", "");
+        assert_eq!(flushes.0.load(Ordering::Relaxed), 1);
+
+        bogger.flush_on(BogLevel::INFO);
+        bogger.bog(BogLevel::DEBUG, "", "");
+        bogger.bog(BogLevel::INFO, "", "");
+        bogger.bog(BogLevel::NOTE, "", "");
+        assert_eq!(flushes.0.load(Ordering::Relaxed), 3);
+    }
+
+    #[test]
+    fn line_label_precedes_color() {
+        let capture = Capture::default();
+        let mut bogger = GLOBAL_BOGGER_STRUCT::new(Box::new(Fg {}), Box::new(capture.clone()));
+        bogger.line_label = Some("cba".into());
+        bogger.bog(BogLevel::INFO, "", "a\nb");
+        assert_eq!(capture.take(), "[cba] \x1b[32m[INFO]\x1b[0m a\n[cba] b\n");
+    }
+
+    #[test]
+    fn levels_by_priority() {
+        let priorities: Vec<_> = BogLevel::all()
+            .iter()
+            .map(|l| l.default_priority())
+            .collect();
+        assert_eq!(priorities, [120, 100, 80, 60, 40, 20]);
+        assert_eq!(Fg {}.priority(&BogLevel::WARN), 80);
+    }
+
+    #[test]
+    fn level_names() {
+        assert_eq!(Fg {}.begin_tag(BogLevel::ERROR), "\x1b[31m[ERRO");
+        assert_eq!(FgFull {}.begin_tag(BogLevel::ERROR), "\x1b[31m[ERROR");
+        assert_eq!(FgFull {}.begin_tag(BogLevel::CUSTOM("ok")), "\x1b[34m[ok");
+
+        assert_eq!(Bg {}.begin_tag(BogLevel::WARN), "\x1b[30;43mWARN ");
+        assert_eq!(BgAbbrev {}.begin_tag(BogLevel::DEBUG), "\x1b[30;45mDBUG");
+        assert_eq!(Bg {}.begin_tag(BogLevel::CUSTOM("ok")), "\x1b[30;44mok");
+    }
+
+    #[test]
+    fn bog_returns_line() {
+        let capture = Capture::default();
+        let mut bogger = GLOBAL_BOGGER_STRUCT::new(Box::new(Logfmt {}), Box::new(capture.clone()));
+        bogger.filter_below(BogLevel::INFO);
+        bogger.suffix = " end".into();
+
+        assert_eq!(
+            bogger.bog(BogLevel::WARN, "", "msg").as_deref(),
+            Some("level=warn msg=msg end")
+        );
+        assert_eq!(bogger.bog(BogLevel::DEBUG, "", "msg"), None);
+        assert_eq!(capture.take(), "level=warn msg=msg end\n");
+    }
 }
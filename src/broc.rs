@@ -1,43 +1,190 @@
 //! Utilities for (spawning) processes
 
-use crate::{bog::BogOkExt, ebog, misc::ResultExt};
+use crate::{
+    bo::{InvalidMode, MapReaderError, ReadStats, map_reader_lines},
+    bog::{BogLevel, BogOkExt},
+    bs::is_executable,
+    ebog,
+    misc::{ResultExt, retry_with_level},
+    nbog, wbog,
+};
 use cfg_if::cfg_if;
 use std::{
     env,
     ffi::{OsStr, OsString},
-    process::{Child, ChildStdout, Command, Stdio},
-    sync::LazyLock,
+    io,
+    path::{Path, PathBuf},
+    process::{Child, ChildStdout, Command, ExitStatus, Stdio},
+    sync::{
+        LazyLock,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread,
+    time::{Duration, Instant},
 };
 
+/// Working directory and environment for a spawned process.
+/// The default inherits both from the current process.
+#[derive(Debug, Default, Clone)]
+pub struct SpawnOpts {
+    current_dir: Option<PathBuf>,
+    clear_env: bool,
+    envs: EnvVars,
+}
+
+impl SpawnOpts {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn current_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.current_dir = Some(dir.into());
+        self
+    }
+
+    /// Start from an empty environment instead of inheriting it
+    pub fn clear_env(mut self, clear: bool) -> Self {
+        self.clear_env = clear;
+        self
+    }
+
+    /// Add a variable on top of the inherited (or cleared) environment
+    pub fn env(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.envs.push((name.into(), value.into()));
+        self
+    }
+
+    pub fn apply<'a>(&self, cmd: &'a mut Command) -> &'a mut Command {
+        if self.clear_env {
+            cmd.env_clear();
+        }
+        cmd.envs(self.envs.iter().map(|(k, v)| (k, v)));
+        if let Some(dir) = &self.current_dir {
+            cmd.current_dir(dir);
+        }
+        cmd
+    }
+}
+
+static DRY_RUN: AtomicBool = AtomicBool::new(false);
+
+/// When enabled, every function in this module which runs a command bogs the command at NOTE level instead of running it.
+/// They then return None (or Err), and [`exec_script`] exits with 0.
+/// The `try_spawn_*` functions return Err without bogging.
+pub fn set_dry_run(dry_run: bool) {
+    DRY_RUN.store(dry_run, Ordering::Relaxed);
+}
+
+pub fn is_dry_run() -> bool {
+    DRY_RUN.load(Ordering::Relaxed)
+}
+
+/// Bog cmd if in dry-run mode
+fn dry_run(cmd: &Command) -> bool {
+    if is_dry_run() {
+        nbog!("dry-run"; "{}", command_string(cmd));
+        true
+    } else {
+        false
+    }
+}
+
 /// Execute script using shell and display error
 pub fn spawn_script(
     script: &str,
-    vars: impl IntoIterator<Item = (String, String)>,
+    vars: impl IntoIterator<Item = (impl AsRef<OsStr>, impl AsRef<OsStr>)>,
     stdin: Stdio,
     stdout: Stdio,
     stderr: Stdio,
 ) -> Option<Child> {
-    let (shell, arg) = &*SHELL;
+    spawn_script_with(script, vars, &SpawnOpts::default(), stdin, stdout, stderr)
+}
+
+/// [`spawn_script`] with [`SpawnOpts`]. vars are applied on top of the environment in opts.
+pub fn spawn_script_with(
+    script: &str,
+    vars: impl IntoIterator<Item = (impl AsRef<OsStr>, impl AsRef<OsStr>)>,
+    opts: &SpawnOpts,
+    stdin: Stdio,
+    stdout: Stdio,
+    stderr: Stdio,
+) -> Option<Child> {
+    let cmd = script_command(script, vars, opts);
+    if dry_run(&cmd) {
+        return None;
+    }
+
+    spawn_script_command(cmd, script, stdin, stdout, stderr).or_err()
+}
+
+/// [`spawn_script`], returning the error instead of bogging it
+pub fn try_spawn_script(
+    script: &str,
+    vars: impl IntoIterator<Item = (impl AsRef<OsStr>, impl AsRef<OsStr>)>,
+    stdin: Stdio,
+    stdout: Stdio,
+    stderr: Stdio,
+) -> Result<Child, String> {
+    try_spawn_script_with(script, vars, &SpawnOpts::default(), stdin, stdout, stderr)
+}
+
+/// [`spawn_script_with`], returning the error instead of bogging it
+pub fn try_spawn_script_with(
+    script: &str,
+    vars: impl IntoIterator<Item = (impl AsRef<OsStr>, impl AsRef<OsStr>)>,
+    opts: &SpawnOpts,
+    stdin: Stdio,
+    stdout: Stdio,
+    stderr: Stdio,
+) -> Result<Child, String> {
+    let cmd = script_command(script, vars, opts);
+    if is_dry_run() {
+        return Err(dry_run_err(&cmd));
+    }
 
-    Command::new(shell)
-        .arg(arg)
-        .arg(script)
-        .envs(vars)
-        .stdin(stdin)
+    spawn_script_command(cmd, script, stdin, stdout, stderr)
+}
+
+fn spawn_script_command(
+    mut cmd: Command,
+    script: &str,
+    stdin: Stdio,
+    stdout: Stdio,
+    stderr: Stdio,
+) -> Result<Child, String> {
+    cmd.stdin(stdin)
         .stdout(stdout)
         .stderr(stderr)
         .spawn()
         .prefix_err(&format!("Could not spawn: {script}"))
-        .or_err()
 }
 
-pub fn exec_script(script: &str, vars: impl IntoIterator<Item = (String, String)>) -> ! {
+fn script_command(
+    script: &str,
+    vars: impl IntoIterator<Item = (impl AsRef<OsStr>, impl AsRef<OsStr>)>,
+    opts: &SpawnOpts,
+) -> Command {
+    let (shell, arg) = &*SHELL;
+
+    let mut cmd = Command::new(shell);
+    opts.apply(&mut cmd).arg(arg).arg(script).envs(vars);
+    cmd
+}
+
+pub fn exec_script(
+    script: &str,
+    vars: impl IntoIterator<Item = (impl AsRef<OsStr>, impl AsRef<OsStr>)>,
+) -> ! {
     let (shell, arg) = &*SHELL;
 
     let mut cmd = Command::new(shell);
     cmd.arg(arg).arg(script).envs(vars);
 
-    #[cfg(not(windows))]
+    if dry_run(&cmd) {
+        std::process::exit(0);
+    }
+
+    #[cfg(unix)]
     {
         // replace current process
 
@@ -51,7 +198,10 @@ pub fn exec_script(script: &str, vars: impl IntoIterator<Item = (String, String)
 
     #[cfg(windows)]
     {
-        match command.status() {
+        // no exec on windows: run to completion and forward the exit code
+        use std::process::exit;
+
+        match cmd.status() {
             Ok(status) => {
                 exit(
                     status
@@ -60,24 +210,33 @@ pub fn exec_script(script: &str, vars: impl IntoIterator<Item = (String, String)
                 );
             }
             Err(err) => {
-                ebog!("Could not exec {cmd:?}: {err}");
+                ebog!("Could not exec {script:?}: {err}");
                 exit(1);
             }
         }
     }
+
+    #[cfg(not(any(unix, windows)))]
+    {
+        ebog!("Could not exec {script:?}: unsupported platform.");
+        std::process::exit(1);
+    }
 }
 
 /// One-off spawn executable
 pub fn spawn_detached(cmd: &mut Command) -> Option<Child> {
-    let err_prefix = format!(
-        "Failed to spawn: {}",
-        format_sh_command({
-            let mut inputs = vec![cmd.get_program()];
-            inputs.extend(cmd.get_args());
-            inputs
-        })
-        .to_string_lossy()
-    );
+    if dry_run(cmd) {
+        return None;
+    }
+
+    try_spawn_detached(cmd).or_err()
+}
+
+/// [`spawn_detached`], returning the error instead of bogging it
+pub fn try_spawn_detached(cmd: &mut Command) -> Result<Child, String> {
+    if is_dry_run() {
+        return Err(dry_run_err(cmd));
+    }
 
     cmd.stdin(Stdio::null())
         .stdout(Stdio::null())
@@ -101,42 +260,532 @@ pub fn spawn_detached(cmd: &mut Command) -> Option<Child> {
 
             cmd.creation_flags(DETACHED_PROCESS | CREATE_NEW_PROCESS_GROUP);
         } else {
-            return None;
+            return Err(format!("{}: unsupported platform", spawn_err_prefix(cmd)));
         }
     }
 
-    cmd.spawn().prefix_err(&err_prefix).or_err()
+    cmd.spawn().prefix_err_with(|| spawn_err_prefix(cmd))
+}
+
+/// [`spawn_detached`] with [`SpawnOpts`]
+pub fn spawn_detached_with(cmd: &mut Command, opts: &SpawnOpts) -> Option<Child> {
+    spawn_detached(opts.apply(cmd))
+}
+
+/// [`spawn_piped`] with [`SpawnOpts`]
+pub fn spawn_piped_with(cmd: &mut Command, opts: &SpawnOpts) -> Result<ChildStdout, String> {
+    spawn_piped(opts.apply(cmd))
 }
 
 pub fn spawn_piped(cmd: &mut Command) -> Result<ChildStdout, String> {
-    let err_prefix = format!(
-        "Failed to spawn: {}",
-        format_sh_command({
-            let mut inputs = vec![cmd.get_program()];
-            inputs.extend(cmd.get_args());
-            inputs
-        })
-        .to_string_lossy()
-    );
+    if dry_run(cmd) {
+        return Err(dry_run_err(cmd));
+    }
 
     match cmd
         .stdin(Stdio::null())
         .stdout(Stdio::piped())
         .stderr(Stdio::null())
         .spawn()
-        .prefix_err(&err_prefix)?
+        .prefix_err_with(|| spawn_err_prefix(cmd))?
         .stdout
         .take()
     {
         Some(s) => Ok(s),
-        None => Err(err_prefix), // stdout failure has no reason suffix
+        None => Err(spawn_err_prefix(cmd)), // stdout failure has no reason suffix
+    }
+}
+
+/// [`spawn_detached`], returning a [`ChildGuard`] which kills the child's process group on drop.
+pub fn spawn_detached_guarded(cmd: &mut Command) -> Option<ChildGuard> {
+    let child = spawn_detached(cmd)?;
+    Some(ChildGuard {
+        child: Some(child),
+        on_drop: DropAction::Kill,
+        group: cfg!(unix), // setsid makes the child a group leader
+    })
+}
+
+/// [`spawn_piped`], returning a [`ChildGuard`] which kills the child on drop.
+/// Take stdout from the guard with `guard.stdout.take()`.
+pub fn spawn_piped_guarded(cmd: &mut Command) -> Result<ChildGuard, String> {
+    if dry_run(cmd) {
+        return Err(dry_run_err(cmd));
+    }
+
+    let child = cmd
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .prefix_err_with(|| spawn_err_prefix(cmd))?;
+    Ok(ChildGuard::new(child))
+}
+
+/// Spawn cmds as a pipeline like `a | b | c`.
+/// The first command's stdin and every command's stderr are null.
+/// If a command can't be spawned, those already running are killed.
+pub fn spawn_pipeline(cmds: Vec<Command>) -> Result<Pipeline, String> {
+    if is_dry_run() {
+        let pipeline = cmds
+            .iter()
+            .map(command_string)
+            .collect::<Vec<_>>()
+            .join(" | ");
+        nbog!("dry-run"; "{pipeline}");
+        return Err(format!("Dry run: {pipeline}"));
+    }
+
+    let mut children: Vec<ChildGuard> = Vec::with_capacity(cmds.len());
+    let mut stdout: Option<ChildStdout> = None;
+
+    for mut cmd in cmds {
+        let stdin = stdout.take().map_or_else(Stdio::null, Stdio::from);
+        let mut child = cmd
+            .stdin(stdin)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .prefix_err_with(|| spawn_err_prefix(&cmd))?; // dropping children kills them
+
+        stdout = child.stdout.take();
+        children.push(ChildGuard::new(child));
+    }
+
+    if children.is_empty() {
+        return Err("Failed to spawn: empty pipeline".to_string());
+    }
+    Ok(Pipeline {
+        stdout,
+        children: children
+            .into_iter()
+            .map(|c| c.on_drop(DropAction::Wait))
+            .collect(),
+    })
+}
+
+/// The commands of a pipeline spawned by [`spawn_pipeline`].
+/// When dropped, stdout is closed and then every command is waited on.
+pub struct Pipeline {
+    /// The stdout of the last command
+    pub stdout: Option<ChildStdout>,
+    // declared after stdout so that it is dropped first, letting the children exit
+    children: Vec<ChildGuard>,
+}
+
+impl Pipeline {
+    /// The spawned commands, in order
+    pub fn children(&self) -> &[ChildGuard] {
+        &self.children
+    }
+
+    /// Close stdout (unless it was taken) and wait for every command, returning their exit statuses in order
+    pub fn wait(mut self) -> io::Result<Vec<ExitStatus>> {
+        self.stdout.take();
+        self.children.iter_mut().map(|c| c.wait()).collect()
+    }
+}
+
+/// What a [`ChildGuard`] does with its child when dropped
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DropAction {
+    /// Kill and reap the child
+    #[default]
+    Kill,
+    /// Wait for the child to exit
+    Wait,
+}
+
+/// Holds a [`Child`], killing (or waiting on) it when dropped.
+/// Derefs to the child.
+pub struct ChildGuard {
+    child: Option<Child>,
+    on_drop: DropAction,
+    group: bool,
+}
+
+impl ChildGuard {
+    pub fn new(child: Child) -> Self {
+        Self {
+            child: Some(child),
+            on_drop: DropAction::default(),
+            group: false,
+        }
+    }
+
+    pub fn on_drop(mut self, action: DropAction) -> Self {
+        self.on_drop = action;
+        self
+    }
+
+    /// Release the child without killing it
+    pub fn into_inner(mut self) -> Child {
+        self.child.take().unwrap()
+    }
+}
+
+impl std::ops::Deref for ChildGuard {
+    type Target = Child;
+
+    fn deref(&self) -> &Child {
+        self.child.as_ref().unwrap()
+    }
+}
+
+impl std::ops::DerefMut for ChildGuard {
+    fn deref_mut(&mut self) -> &mut Child {
+        self.child.as_mut().unwrap()
+    }
+}
+
+impl Drop for ChildGuard {
+    fn drop(&mut self) {
+        let Some(mut child) = self.child.take() else {
+            return;
+        };
+
+        if self.on_drop == DropAction::Kill {
+            #[cfg(unix)]
+            if self.group {
+                // negative pid targets the process group
+                unsafe {
+                    libc::kill(-(child.id() as libc::pid_t), libc::SIGKILL);
+                }
+            }
+            let _ = child.kill();
+        }
+        let _ = child.wait();
+    }
+}
+
+/// Spawn cmd and pass each line of its stdout to f (see [`map_reader_lines`]).
+/// The child is reaped once f returns an error or stdout is exhausted.
+/// Returns None (after logging) if the child could not be spawned or waited on.
+pub fn spawn_and_map_lines<E>(
+    cmd: &mut Command,
+    f: impl FnMut(String) -> Result<(), E>,
+) -> Option<(ExitStatus, Result<ReadStats, MapReaderError<E>>)> {
+    if dry_run(cmd) {
+        return None;
+    }
+
+    let mut child = cmd
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .spawn()
+        .prefix_err_with(|| spawn_err_prefix(cmd))
+        .or_err()?;
+
+    let result = match child.stdout.take() {
+        // stdout is dropped after reading, so the child won't block on a full pipe
+        Some(stdout) => map_reader_lines(stdout, None, InvalidMode::default(), f),
+        None => Ok(ReadStats::default()),
+    };
+
+    let status = child
+        .wait()
+        .prefix_err_with(|| spawn_err_prefix(cmd))
+        .or_err()?;
+    Some((status, result))
+}
+
+/// Run cmd to completion and return its stdout (lossily decoded, without the trailing newline), like `$(cmd)`.
+/// Returns None (after logging) if cmd could not be spawned or exited unsuccessfully.
+pub fn capture_stdout(cmd: &mut Command) -> Option<String> {
+    let (stdout, status) = capture_stdout_status(cmd)?;
+    if status.success() {
+        Some(stdout)
+    } else {
+        ebog!("{} exited with {status}", command_string(cmd));
+        None
+    }
+}
+
+/// [`capture_stdout`], but also return the exit status instead of failing on unsuccessful exit.
+pub fn capture_stdout_status(cmd: &mut Command) -> Option<(String, ExitStatus)> {
+    if dry_run(cmd) {
+        return None;
+    }
+
+    let output = cmd
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .spawn()
+        .and_then(|child| child.wait_with_output())
+        .prefix_err_with(|| spawn_err_prefix(cmd))
+        .or_err()?;
+
+    let mut stdout = String::from_utf8_lossy(&output.stdout).into_owned();
+    trim_newline(&mut stdout);
+    Some((stdout, output.status))
+}
+
+/// Spawn the Command built by cmd_builder, retrying up to attempts times on spawn failure.
+/// The delay starts at backoff and doubles after each failure (see [`retry_with_level`]).
+/// Failed attempts are logged as warnings, the final failure as an error.
+pub fn spawn_retry(
+    cmd_builder: impl Fn() -> Command,
+    attempts: u32,
+    backoff: Duration,
+) -> Option<Child> {
+    if dry_run(&cmd_builder()) {
+        return None;
     }
+
+    retry_with_level(attempts, backoff, BogLevel::WARN, || {
+        let mut cmd = cmd_builder();
+        cmd.spawn().prefix_err_with(|| spawn_err_prefix(&cmd))
+    })
+    .or_err()
+}
+
+/// [`capture_stdout`] with the retry behavior of [`spawn_retry`].
+/// Unsuccessful exits are also retried.
+pub fn capture_stdout_retry(
+    cmd_builder: impl Fn() -> Command,
+    attempts: u32,
+    backoff: Duration,
+) -> Option<String> {
+    if dry_run(&cmd_builder()) {
+        return None;
+    }
+
+    retry_with_level(attempts, backoff, BogLevel::WARN, || {
+        let mut cmd = cmd_builder();
+        let output = cmd
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .spawn()
+            .and_then(|child| child.wait_with_output())
+            .prefix_err_with(|| spawn_err_prefix(&cmd))?;
+
+        if !output.status.success() {
+            return Err(format!(
+                "{} exited with {}",
+                command_string(&cmd),
+                output.status
+            ));
+        }
+        let mut stdout = String::from_utf8_lossy(&output.stdout).into_owned();
+        trim_newline(&mut stdout);
+        Ok(stdout)
+    })
+    .or_err()
+}
+
+/// Run cmd to completion with input on its stdin, returning its stdout, like `printf input | cmd`.
+///
+/// Input is written from a separate thread: writing it all before reading stdout would deadlock
+/// once the child blocks on a full stdout pipe while we block on a full stdin pipe.
+/// A child exiting without reading all of its input (BrokenPipe) is not an error.
+/// Returns None (after logging) if cmd could not be spawned, written to or waited on.
+pub fn run_with_stdin(cmd: &mut Command, input: &[u8]) -> Option<(Vec<u8>, ExitStatus)> {
+    if dry_run(cmd) {
+        return None;
+    }
+
+    let mut child = cmd
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .prefix_err_with(|| spawn_err_prefix(cmd))
+        .or_err()?;
+
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    let output = thread::scope(|s| {
+        let writer = s.spawn(move || {
+            // stdin is dropped (closed) at the end of this closure
+            match io::Write::write_all(&mut stdin, input) {
+                Err(e) if e.kind() != io::ErrorKind::BrokenPipe => Err(e),
+                _ => Ok(()),
+            }
+        });
+        let output = child.wait_with_output();
+        let written = writer
+            .join()
+            .unwrap_or_else(|_| Err(io::Error::other("stdin writer panicked")));
+        written.and(output)
+    })
+    .prefix_err_with(|| format!("Failed to run: {}", command_string(cmd)))
+    .or_err()?;
+
+    Some((output.stdout, output.status))
+}
+
+/// Run cmd to completion, capturing stdout and stderr separately.
+/// Both pipes are drained concurrently (by [`Child::wait_with_output`]), so a child filling one of them can't deadlock.
+/// Returns None (after logging) if cmd could not be spawned or waited on.
+pub fn capture_output(cmd: &mut Command) -> Option<(Vec<u8>, Vec<u8>, ExitStatus)> {
+    if dry_run(cmd) {
+        return None;
+    }
+
+    let output = cmd
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .and_then(|child| child.wait_with_output())
+        .prefix_err_with(|| spawn_err_prefix(cmd))
+        .or_err()?;
+
+    Some((output.stdout, output.stderr, output.status))
+}
+
+/// Wait up to timeout for child to exit, killing it (SIGKILL on unix, TerminateProcess on windows) on expiry.
+/// Returns None if the child was killed or could not be waited on.
+pub fn wait_timeout(child: &mut Child, timeout: Duration) -> Option<ExitStatus> {
+    let deadline = Instant::now() + timeout;
+    let mut interval = Duration::from_millis(1);
+
+    loop {
+        match child.try_wait() {
+            Ok(Some(status)) => return Some(status),
+            Ok(None) => {}
+            Err(e) => {
+                ebog!("Failed to wait on child {}: {e}", child.id());
+                return None;
+            }
+        }
+
+        let now = Instant::now();
+        if now >= deadline {
+            break;
+        }
+        thread::sleep(interval.min(deadline - now));
+        interval = (interval * 2).min(Duration::from_millis(50));
+    }
+
+    wbog!("Child {} timed out after {timeout:?}, killing", child.id());
+    let _ = child.kill();
+    let _ = child.wait(); // reap
+    None
+}
+
+/// [`capture_stdout`], but kill the child and return None if it doesn't exit within timeout.
+pub fn capture_stdout_timeout(cmd: &mut Command, timeout: Duration) -> Option<String> {
+    if dry_run(cmd) {
+        return None;
+    }
+
+    let mut child = cmd
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .spawn()
+        .prefix_err_with(|| spawn_err_prefix(cmd))
+        .or_err()?;
+
+    // read on a separate thread so that a full pipe can't block the child
+    let reader = child.stdout.take().map(|mut stdout| {
+        thread::spawn(move || {
+            let mut buf = Vec::new();
+            let _ = io::Read::read_to_end(&mut stdout, &mut buf);
+            buf
+        })
+    });
+
+    // on timeout, the reader is left to finish by itself since grandchildren may still hold the pipe open
+    let status = wait_timeout(&mut child, timeout)?;
+    let buf = reader.and_then(|r| r.join().ok()).unwrap_or_default();
+
+    if !status.success() {
+        ebog!("{} exited with {status}", command_string(cmd));
+        return None;
+    }
+
+    let mut stdout = String::from_utf8_lossy(&buf).into_owned();
+    trim_newline(&mut stdout);
+    Some(stdout)
+}
+
+fn trim_newline(s: &mut String) {
+    if s.ends_with('\n') {
+        s.pop();
+        if s.ends_with('\r') {
+            s.pop();
+        }
+    }
+}
+
+/// Find the first executable named name in PATH.
+/// Names containing a path separator are checked directly.
+/// On windows, the extensions in PATHEXT are also tried.
+pub fn which(name: &str) -> Option<PathBuf> {
+    let is_match = |p: &Path| p.is_file() && is_executable(p);
+
+    let candidates = |p: PathBuf| {
+        #[cfg(windows)]
+        {
+            let mut ret = vec![];
+            if p.extension().is_none() {
+                let pathext = env::var("PATHEXT").unwrap_or_else(|_| ".COM;.EXE;.BAT;.CMD".into());
+                for ext in pathext.split(';').filter(|e| !e.is_empty()) {
+                    let mut with_ext = p.clone().into_os_string();
+                    with_ext.push(ext);
+                    ret.push(PathBuf::from(with_ext));
+                }
+            }
+            ret.push(p);
+            ret
+        }
+        #[cfg(not(windows))]
+        {
+            vec![p]
+        }
+    };
+
+    if Path::new(name).components().count() > 1 {
+        return candidates(name.into()).into_iter().find(|p| is_match(p));
+    }
+
+    let path_var = env::var_os("PATH")?;
+    env::split_paths(&path_var)
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .flat_map(|dir| candidates(dir.join(name)))
+        .find(|p| is_match(p))
 }
 
-/// Join arguments into a single string
+fn spawn_err_prefix(cmd: &Command) -> String {
+    format!("Failed to spawn: {}", command_string(cmd))
+}
+
+fn dry_run_err(cmd: &Command) -> String {
+    format!("Dry run: {}", command_string(cmd))
+}
+
+fn command_string(cmd: &Command) -> String {
+    format_command(cmd).to_string_lossy().into_owned()
+}
+
+/// Join arguments into a single string, quoted for [`SHELL`]:
+/// POSIX single quotes on unix, and cmd.exe or PowerShell quoting on windows.
 /// Non-UTF-8 arguments are not escaped
-/// Todo: support windows
 pub fn format_sh_command(inputs: Vec<impl AsRef<OsStr>>) -> OsString {
+    join_args(inputs, false)
+}
+
+/// [`format_sh_command`], but only quote arguments which need it, e.g. `ls -la 'a b'`.
+/// Intended for displaying commands as a human would type them.
+pub fn format_sh_command_pretty(inputs: Vec<impl AsRef<OsStr>>) -> OsString {
+    join_args(inputs, true)
+}
+
+/// Format each command with [`format_sh_command`] and join them with sep, e.g. `"\n"` or `" && "`.
+/// No separator is added after the last command.
+pub fn join_commands(cmds: &[Vec<impl AsRef<OsStr>>], sep: &str) -> OsString {
+    let mut script = OsString::new();
+
+    for (i, cmd) in cmds.iter().enumerate() {
+        if i > 0 {
+            script.push(sep);
+        }
+        script.push(join_args(cmd, false));
+    }
+
+    script
+}
+
+fn join_args(inputs: impl IntoIterator<Item = impl AsRef<OsStr>>, pretty: bool) -> OsString {
     let mut cmd = OsString::new();
     let mut first = true;
 
@@ -151,10 +800,11 @@ pub fn format_sh_command(inputs: Vec<impl AsRef<OsStr>>) -> OsString {
         match os.to_str() {
             Some(s) => {
                 // shell-escape only when valid UTF-8
-                let escaped = s.replace('\'', "'\\''");
-                cmd.push("'");
-                cmd.push(escaped);
-                cmd.push("'");
+                if pretty && !needs_quote(s) {
+                    cmd.push(s);
+                } else {
+                    cmd.push(quote_arg(s));
+                }
             }
             None => {
                 cmd.push(os);
@@ -165,6 +815,60 @@ pub fn format_sh_command(inputs: Vec<impl AsRef<OsStr>>) -> OsString {
     cmd
 }
 
+/// [`format_sh_command`] for the program and arguments of cmd (its env and working directory are not included)
+pub fn format_command(cmd: &Command) -> OsString {
+    let mut inputs = vec![cmd.get_program()];
+    inputs.extend(cmd.get_args());
+    format_sh_command(inputs)
+}
+
+/// Whether s is empty or contains characters which may be special to the shell
+fn needs_quote(s: &str) -> bool {
+    #[cfg(not(windows))]
+    const SAFE: &str = "_-./:=@%+,";
+    #[cfg(windows)]
+    const SAFE: &str = "_-./:=@+,\\";
+
+    s.is_empty()
+        || !s
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || SAFE.contains(c))
+}
+
+#[cfg(not(windows))]
+fn quote_arg(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "'\\''"))
+}
+
+#[cfg(windows)]
+fn quote_arg(s: &str) -> String {
+    let (shell, _) = &*SHELL;
+    let shell = shell.to_lowercase();
+
+    if shell.contains("powershell") || shell.contains("pwsh") {
+        // single-quoted strings are literal, with '' for '
+        return format!("'{}'", s.replace('\'', "''"));
+    }
+
+    // cmd.exe: metacharacters are inert inside double quotes, and " is escaped as "".
+    // Variable expansion still happens inside quotes, so step outside them to caret-escape % and !
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\"\""),
+            '%' | '!' => {
+                quoted.push_str("\"^");
+                quoted.push(c);
+                quoted.push('"');
+            }
+            _ => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
 // SHELL
 pub static SHELL: LazyLock<(String, String)> = LazyLock::new(|| {
     #[cfg(windows)]
@@ -198,3 +902,287 @@ macro_rules! env_vars {
         )
     };
 }
+
+/// Environment variables which may not be valid UTF-8
+pub type EnvOsVars = Vec<(OsString, OsString)>;
+
+/// [`env_vars!`] for [`EnvOsVars`]
+#[macro_export]
+macro_rules! env_os_vars {
+    ($( $name:expr => $value:expr ),* $(,)?) => {
+        Vec::<(std::ffi::OsString, std::ffi::OsString)>::from([
+            $( ($name.into(), $value.into()) ),*
+            ]
+        )
+    };
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn map_lines_of_child() {
+        let mut lines = vec![];
+        let (status, result) = spawn_and_map_lines::<()>(
+            Command::new("sh").args(["-c", "printf 'a\\nb\\n'; exit 3"]),
+            |l| {
+                lines.push(l);
+                Ok(())
+            },
+        )
+        .unwrap();
+
+        assert!(result.is_ok());
+        assert_eq!(status.code(), Some(3));
+        assert_eq!(lines, ["a", "b"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn capture_child_stdout() {
+        assert_eq!(
+            capture_stdout(Command::new("echo").arg("hello")).as_deref(),
+            Some("hello")
+        );
+        assert_eq!(capture_stdout(&mut Command::new("false")), None);
+
+        let (stdout, status) =
+            capture_stdout_status(Command::new("sh").args(["-c", "echo out; exit 2"])).unwrap();
+        assert_eq!(stdout, "out");
+        assert_eq!(status.code(), Some(2));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn timeouts() {
+        let mut child = Command::new("sleep").arg("10").spawn().unwrap();
+        let start = Instant::now();
+        assert!(wait_timeout(&mut child, Duration::from_millis(50)).is_none());
+        assert!(start.elapsed() < Duration::from_secs(5));
+
+        let mut child = Command::new("true").spawn().unwrap();
+        assert!(
+            wait_timeout(&mut child, Duration::from_secs(5))
+                .unwrap()
+                .success()
+        );
+
+        assert_eq!(
+            capture_stdout_timeout(Command::new("echo").arg("hi"), Duration::from_secs(5))
+                .as_deref(),
+            Some("hi")
+        );
+        assert_eq!(
+            capture_stdout_timeout(
+                Command::new("sh").args(["-c", "echo early; sleep 10"]),
+                Duration::from_millis(50)
+            ),
+            None
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn format_sh_command_pretty_quotes() {
+        assert_eq!(
+            format_sh_command_pretty(vec!["ls", "-la", "--color=auto", "./a-b_c/d.txt"]),
+            OsString::from("ls -la --color=auto ./a-b_c/d.txt")
+        );
+        assert_eq!(
+            format_sh_command_pretty(vec!["echo", "it's", "a b", "", "$HOME", "*"]),
+            OsString::from("echo 'it'\\''s' 'a b' '' '$HOME' '*'")
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn format_sh_command_quotes() {
+        assert_eq!(
+            format_sh_command(vec!["echo", "it's", "a b"]),
+            OsString::from("'echo' 'it'\\''s' 'a b'")
+        );
+        assert_eq!(
+            format_command(Command::new("echo").args(["it's", "a b"])),
+            OsString::from("'echo' 'it'\\''s' 'a b'")
+        );
+        assert_eq!(
+            join_commands(&[vec!["cd", "a b"], vec!["ls"]], " && "),
+            OsString::from("'cd' 'a b' && 'ls'")
+        );
+        assert_eq!(join_commands(&[] as &[Vec<&str>], "\n"), OsString::new());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn spawn_opts_env_and_dir() {
+        let opts = SpawnOpts::new()
+            .current_dir("/")
+            .clear_env(true)
+            .env("CBA_A", "1")
+            .env("CBA_B", "2");
+
+        let mut cmd = Command::new("/bin/sh");
+        cmd.args(["-c", "echo \"$PWD $CBA_A $CBA_B ${HOME:-unset}\""]);
+        assert_eq!(
+            capture_stdout(opts.apply(&mut cmd)).as_deref(),
+            Some("/ 1 2 unset")
+        );
+
+        let child = spawn_script_with(
+            "test \"$CBA_A\" = 3",
+            env_vars!("CBA_A" => "3"),
+            &opts,
+            Stdio::null(),
+            Stdio::null(),
+            Stdio::null(),
+        );
+        assert!(child.unwrap().wait().unwrap().success());
+
+        use std::os::unix::ffi::OsStringExt;
+        let child = spawn_script(
+            "test \"$CBA_RAW\" = \"$(printf 'a\\377')\"",
+            env_os_vars!("CBA_RAW" => OsString::from_vec(b"a\xff".to_vec())),
+            Stdio::null(),
+            Stdio::null(),
+            Stdio::null(),
+        );
+        assert!(child.unwrap().wait().unwrap().success());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn capture_output_separates_streams() {
+        // more than a pipe buffer on both streams
+        let script = "head -c 200000 /dev/zero; head -c 200000 /dev/zero >&2; exit 1";
+        let (stdout, stderr, status) =
+            capture_output(Command::new("sh").args(["-c", script])).unwrap();
+        assert_eq!(stdout.len(), 200000);
+        assert_eq!(stderr.len(), 200000);
+        assert_eq!(status.code(), Some(1));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn pipeline_connects_commands() {
+        let mut printf = Command::new("printf");
+        printf.arg("b\\na\\n");
+        let mut head = Command::new("head");
+        head.arg("-n1");
+        let mut out = String::new();
+        let mut pipeline = spawn_pipeline(vec![printf, Command::new("sort"), head]).unwrap();
+        io::Read::read_to_string(pipeline.stdout.as_mut().unwrap(), &mut out).unwrap();
+        assert_eq!(out, "a\n");
+        let statuses = pipeline.wait().unwrap();
+        assert_eq!(statuses.len(), 3);
+        assert!(statuses.iter().all(|s| s.success()));
+
+        let pipeline = spawn_pipeline(vec![Command::new("true"), Command::new("false")]).unwrap();
+        let pids: Vec<_> = pipeline.children().iter().map(|c| c.id()).collect();
+        let statuses = pipeline.wait().unwrap();
+        assert!(statuses[0].success() && !statuses[1].success());
+        // reaped, so the pids no longer exist
+        assert!(
+            pids.iter()
+                .all(|&pid| unsafe { libc::kill(pid as libc::pid_t, 0) } == -1)
+        );
+
+        let err = spawn_pipeline(vec![Command::new("true"), Command::new("/nonexistent/cba")]);
+        assert!(
+            err.err()
+                .unwrap()
+                .starts_with("Failed to spawn: '/nonexistent/cba'")
+        );
+        assert!(spawn_pipeline(vec![]).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn child_guard_kills_on_drop() {
+        let guard = spawn_piped_guarded(Command::new("sleep").arg("10")).unwrap();
+        let pid = guard.id() as libc::pid_t;
+        drop(guard);
+        // reaped, so the pid no longer exists
+        assert_eq!(unsafe { libc::kill(pid, 0) }, -1);
+
+        let mut guard = spawn_piped_guarded(Command::new("echo").arg("hi"))
+            .unwrap()
+            .on_drop(DropAction::Wait);
+        let mut out = String::new();
+        io::Read::read_to_string(&mut guard.stdout.take().unwrap(), &mut out).unwrap();
+        assert_eq!(out, "hi\n");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn retry_until_success() {
+        let counter = env::temp_dir().join(format!("cba_retry_{}", std::process::id()));
+        let _ = std::fs::remove_file(&counter);
+
+        // fails until the counter file has 3 lines
+        let build = || {
+            let mut cmd = Command::new("/bin/sh");
+            cmd.args([
+                "-c",
+                "echo >> \"$0\"; n=$(wc -l < \"$0\"); [ $n -ge 3 ] && echo $n",
+            ])
+            .arg(&counter);
+            cmd
+        };
+        assert_eq!(
+            capture_stdout_retry(build, 2, Duration::from_millis(1)),
+            None
+        );
+        assert_eq!(
+            capture_stdout_retry(build, 3, Duration::from_millis(1)).as_deref(),
+            Some("3")
+        );
+
+        assert!(spawn_retry(|| Command::new("/nonexistent/cba"), 2, Duration::ZERO).is_none());
+        let _ = std::fs::remove_file(&counter);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn run_with_stdin_pipes_input() {
+        // larger than a pipe buffer, so writing and reading have to overlap
+        let input = "line\n".repeat(100_000);
+        let (out, status) = run_with_stdin(&mut Command::new("cat"), input.as_bytes()).unwrap();
+        assert!(status.success());
+        assert_eq!(out, input.as_bytes());
+
+        // child exits without reading
+        let (out, status) = run_with_stdin(&mut Command::new("true"), input.as_bytes()).unwrap();
+        assert!(status.success());
+        assert!(out.is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn try_spawn_returns_errors() {
+        let err = try_spawn_detached(&mut Command::new("/nonexistent/cba")).unwrap_err();
+        assert!(
+            err.starts_with("Failed to spawn: '/nonexistent/cba'"),
+            "{err}"
+        );
+
+        let child = try_spawn_script(
+            "exit 3",
+            env_vars!(),
+            Stdio::null(),
+            Stdio::null(),
+            Stdio::null(),
+        );
+        assert_eq!(child.unwrap().wait().unwrap().code(), Some(3));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn which_finds_sh() {
+        let sh = which("sh").unwrap();
+        assert!(sh.is_absolute());
+        assert_eq!(which(sh.to_str().unwrap()), Some(sh));
+        assert_eq!(which("cba-surely-not-a-command"), None);
+    }
+}
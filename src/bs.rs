@@ -1,11 +1,13 @@
 //! Filesystem set, check, read
 
-use crate::bog::BogOkExt;
-use crate::{ebog, get_or_err, ibog};
+use crate::bath::PathExt;
+use crate::bog::{BogLevel, BogOkExt, bog};
+use crate::misc::ResultExt;
+use crate::{dbog, ebog, get_or_err, ibog, wbog};
 use std::cmp::Ordering;
 use std::path::PathBuf;
 use std::{
-    fs::{self, DirEntry},
+    fs::{self, DirEntry, Metadata},
     path::Path,
 };
 
@@ -66,6 +68,42 @@ pub fn set_executable(path: impl AsRef<Path>) -> bool {
     }
 }
 
+/// Set the exact permission bits of path, e.g. `0o600`.
+/// Windows has no mode bits, so this does nothing there.
+pub fn set_mode(path: impl AsRef<Path>, mode: u32) -> bool {
+    let path = path.as_ref();
+    let error_prefix = format!("Failed to set mode of {path:?}");
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        get_or_err!(
+            fs::set_permissions(path, fs::Permissions::from_mode(mode)),
+            error_prefix
+        );
+        true
+    }
+    #[cfg(windows)]
+    {
+        dbog!("Ignoring mode {mode:o} for {path:?} on windows");
+        true
+    }
+    #[cfg(not(any(unix, windows)))]
+    {
+        ebog!("{error_prefix}: unsupported platform.");
+        false
+    }
+}
+
+/// Restrict access to the owner: `0o700` for directories, `0o600` otherwise.
+pub fn make_private(path: impl AsRef<Path>) -> bool {
+    let path = path.as_ref();
+    let error_prefix = format!("Failed to set mode of {path:?}");
+
+    let metadata = get_or_err!(fs::metadata(path), error_prefix);
+    set_mode(path, if metadata.is_dir() { 0o700 } else { 0o600 })
+}
+
 pub fn is_symlink(path: impl AsRef<Path>) -> bool {
     let path = path.as_ref();
     let error_prefix = format!("Failed to check metadata of {path:?}");
@@ -74,6 +112,33 @@ pub fn is_symlink(path: impl AsRef<Path>) -> bool {
     meta.file_type().is_symlink()
 }
 
+/// Check if path is a regular file, without following symlinks
+pub fn is_regular_file(path: impl AsRef<Path>) -> bool {
+    symlink_metadata_if_exists(path.as_ref()).is_some_and(|m| m.is_file())
+}
+
+/// Check if path is a directory, without following symlinks
+pub fn is_directory(path: impl AsRef<Path>) -> bool {
+    symlink_metadata_if_exists(path.as_ref()).is_some_and(|m| m.is_dir())
+}
+
+/// Check if path exists, without following symlinks (a dangling symlink exists)
+pub fn exists_nofollow(path: impl AsRef<Path>) -> bool {
+    symlink_metadata_if_exists(path.as_ref()).is_some()
+}
+
+/// [`fs::symlink_metadata`], logging errors other than NotFound
+fn symlink_metadata_if_exists(path: &Path) -> Option<Metadata> {
+    match fs::symlink_metadata(path) {
+        Ok(metadata) => Some(metadata),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+        Err(e) => {
+            ebog!("Failed to check metadata of {path:?}: {e}");
+            None
+        }
+    }
+}
+
 pub fn symlink(src: impl AsRef<Path>, dst: impl AsRef<Path>) -> bool {
     let src = src.as_ref();
     let dst = dst.as_ref();
@@ -81,8 +146,6 @@ pub fn symlink(src: impl AsRef<Path>, dst: impl AsRef<Path>) -> bool {
 
     #[cfg(unix)]
     {
-        use crate::misc::ResultExt;
-
         std::os::unix::fs::symlink(src, dst)
             .prefix_err(&error_prefix)
             .or_err()
@@ -91,7 +154,9 @@ pub fn symlink(src: impl AsRef<Path>, dst: impl AsRef<Path>) -> bool {
 
     #[cfg(windows)]
     {
-        let metadata = get_or_err!(std::fs::metadata(path), error_prefix);
+        use std::os::windows::fs as windows_fs;
+
+        let metadata = get_or_err!(fs::symlink_metadata(src), error_prefix);
         if metadata.is_dir() {
             windows_fs::symlink_dir(src, dst)
         } else {
@@ -101,6 +166,148 @@ pub fn symlink(src: impl AsRef<Path>, dst: impl AsRef<Path>) -> bool {
         .or_err()
         .is_some()
     }
+
+    #[cfg(not(any(unix, windows)))]
+    {
+        ebog!("{error_prefix}: unsupported platform.");
+        false
+    }
+}
+
+/// Create a hardlink at dst to src.
+/// Hardlinks can't span filesystems.
+pub fn hardlink(src: impl AsRef<Path>, dst: impl AsRef<Path>) -> bool {
+    let src = src.as_ref();
+    let dst = dst.as_ref();
+    let error_prefix = format!("Failed to hardlink {src:?} to {dst:?}");
+
+    fs::hard_link(src, dst)
+        .prefix_err(&error_prefix)
+        .or_err()
+        .is_some()
+}
+
+/// Check whether a and b are hard links to the same file (same device and inode).
+/// Symlinks are not followed, so a symlink is never a hard link of its target.
+/// Returns false (with a debug note) if either can't be checked, e.g. on platforms without inode information.
+pub fn is_hardlink_of(a: impl AsRef<Path>, b: impl AsRef<Path>) -> bool {
+    compare_file_ids(a.as_ref(), b.as_ref(), false)
+}
+
+/// Check whether a and b (following symlinks) refer to the same file:
+/// the same device and inode on unix, or volume and file index on windows.
+/// Returns false (with a debug note) if either can't be checked.
+pub fn is_same_file(a: impl AsRef<Path>, b: impl AsRef<Path>) -> bool {
+    compare_file_ids(a.as_ref(), b.as_ref(), true)
+}
+
+fn compare_file_ids(a: &Path, b: &Path, follow: bool) -> bool {
+    match (file_id(a, follow), file_id(b, follow)) {
+        (Ok(ia), Ok(ib)) => ia == ib,
+        (Err(e), _) | (_, Err(e)) => {
+            dbog!("Can't compare {a:?} and {b:?}: {e}");
+            false
+        }
+    }
+}
+
+/// Identifies the file at path, or the symlink itself unless follow
+fn file_id(path: &Path, follow: bool) -> std::io::Result<(u64, u64)> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        let metadata = if follow {
+            fs::metadata(path)?
+        } else {
+            fs::symlink_metadata(path)?
+        };
+        Ok((metadata.dev(), metadata.ino()))
+    }
+
+    #[cfg(windows)]
+    {
+        use std::os::windows::{fs::OpenOptionsExt, io::AsRawHandle};
+
+        #[repr(C)]
+        #[allow(non_snake_case)]
+        struct BY_HANDLE_FILE_INFORMATION {
+            dwFileAttributes: u32,
+            ftTimes: [u32; 6],
+            dwVolumeSerialNumber: u32,
+            nFileSizeHigh: u32,
+            nFileSizeLow: u32,
+            nNumberOfLinks: u32,
+            nFileIndexHigh: u32,
+            nFileIndexLow: u32,
+        }
+
+        #[link(name = "kernel32")]
+        unsafe extern "system" {
+            fn GetFileInformationByHandle(
+                file: std::os::windows::io::RawHandle,
+                info: *mut BY_HANDLE_FILE_INFORMATION,
+            ) -> i32;
+        }
+
+        const FILE_FLAG_BACKUP_SEMANTICS: u32 = 0x02000000; // needed to open directories
+        const FILE_FLAG_OPEN_REPARSE_POINT: u32 = 0x00200000; // open the symlink itself
+
+        let flags = if follow {
+            FILE_FLAG_BACKUP_SEMANTICS
+        } else {
+            FILE_FLAG_BACKUP_SEMANTICS | FILE_FLAG_OPEN_REPARSE_POINT
+        };
+        let file = fs::OpenOptions::new()
+            .read(true)
+            .custom_flags(flags)
+            .open(path)?;
+        let mut info = std::mem::MaybeUninit::<BY_HANDLE_FILE_INFORMATION>::uninit();
+        if unsafe { GetFileInformationByHandle(file.as_raw_handle(), info.as_mut_ptr()) } == 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        let info = unsafe { info.assume_init() };
+        Ok((
+            info.dwVolumeSerialNumber as u64,
+            ((info.nFileIndexHigh as u64) << 32) | info.nFileIndexLow as u64,
+        ))
+    }
+
+    #[cfg(not(any(unix, windows)))]
+    {
+        let _ = (path, follow);
+        Err(std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            "no file identity on this platform",
+        ))
+    }
+}
+
+/// Whether a and b both exist and are the same file, without logging
+fn same_existing_file(a: &Path, b: &Path) -> bool {
+    matches!((file_id(a, true), file_id(b, true)), (Ok(ia), Ok(ib)) if ia == ib)
+}
+
+/// Whether path is dir or inside it, after resolving symlinks.
+/// path need not exist.
+fn is_within(path: &Path, dir: &Path) -> bool {
+    match (dir.canonicalize(), canonicalize_existing(path)) {
+        (Ok(dir), Some(path)) => path.starts_with(dir),
+        _ => false,
+    }
+}
+
+/// Canonicalize the deepest existing ancestor of path, and append the rest
+fn canonicalize_existing(path: &Path) -> Option<PathBuf> {
+    let path = path.abs_cwd();
+    let mut missing = Vec::new();
+    let mut current = path.as_path();
+    loop {
+        if let Ok(canonical) = current.canonicalize() {
+            return Some(missing.into_iter().rev().fold(canonical, |p, c| p.join(c)));
+        }
+        missing.push(current.file_name()?);
+        current = current.parent()?;
+    }
 }
 
 // ---------- DIRECTORIES -----------------
@@ -128,8 +335,73 @@ pub fn create_dir(dir: impl AsRef<Path>) -> bool {
     }
 }
 
+/// [`create_dir`], creating missing directories with mode `0o700` on unix.
+/// The mode is applied at creation (still subject to the umask), so they are never accessible to others.
+/// Existing directories are left as is.
+pub fn create_dir_secure(dir: impl AsRef<Path>) -> bool {
+    let dir = dir.as_ref();
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::DirBuilderExt;
+
+        if dir.as_os_str().is_empty() {
+            ebog!("Failed to determine directory");
+            return false;
+        }
+
+        if !dir.exists() {
+            match fs::DirBuilder::new()
+                .recursive(true)
+                .mode(0o700)
+                .create(dir)
+            {
+                Ok(_) => {
+                    ibog!("Created directory: {}", dir.display());
+                    true
+                }
+                Err(e) => {
+                    ebog!("Failed to create {:?}: {e}", dir);
+                    false
+                }
+            }
+        } else {
+            true
+        }
+    }
+
+    #[cfg(not(unix))]
+    {
+        create_dir(dir)
+    }
+}
+
+/// Remove the entries of dir for which filter returns true.
+/// Subdirectories are removed with [`fs::remove_dir`], so fail unless empty, see [`clear_directory_recursive`].
 pub fn clear_directory(dir: impl AsRef<Path>, filter: impl Fn(&DirEntry) -> bool) -> bool {
-    let path = dir.as_ref();
+    clear_directory_inner(dir.as_ref(), &filter, false, false)
+}
+
+/// [`clear_directory`], removing subdirectories together with their contents.
+/// filter is only consulted for the entries directly in dir, not the nested contents.
+pub fn clear_directory_recursive(
+    dir: impl AsRef<Path>,
+    filter: impl Fn(&DirEntry) -> bool,
+) -> bool {
+    clear_directory_inner(dir.as_ref(), &filter, true, false)
+}
+
+/// [`clear_directory_recursive`], removing entries with [`force_remove`].
+pub fn clear_directory_force(dir: impl AsRef<Path>, filter: impl Fn(&DirEntry) -> bool) -> bool {
+    clear_directory_inner(dir.as_ref(), &filter, true, true)
+}
+
+fn clear_directory_inner(
+    path: &Path,
+    filter: &dyn Fn(&DirEntry) -> bool,
+    recursive: bool,
+    force: bool,
+) -> bool {
     let error_prefix = format!("Failed to clear directory at {path:?}");
 
     if !path.exists() {
@@ -145,8 +417,16 @@ pub fn clear_directory(dir: impl AsRef<Path>, filter: impl Fn(&DirEntry) -> bool
         }
         let path = entry.path();
 
-        if path.is_dir() {
-            get_or_err!(fs::remove_dir(&path), error_prefix)
+        if force {
+            if !force_remove(&path) {
+                return false;
+            }
+        } else if path.is_dir() {
+            if recursive {
+                get_or_err!(fs::remove_dir_all(&path), error_prefix)
+            } else {
+                get_or_err!(fs::remove_dir(&path), error_prefix)
+            }
         } else {
             get_or_err!(fs::remove_file(&path), error_prefix)
         }
@@ -154,26 +434,646 @@ pub fn clear_directory(dir: impl AsRef<Path>, filter: impl Fn(&DirEntry) -> bool
     true
 }
 
+/// Remove path (recursively if it is a directory), first clearing read-only permissions which would prevent it:
+/// on windows read-only files can't be removed, on unix entries can't be removed from a read-only directory.
+pub fn force_remove(path: impl AsRef<Path>) -> bool {
+    let path = path.as_ref();
+    let error_prefix = format!("Failed to remove {path:?}");
+
+    let metadata = get_or_err!(fs::symlink_metadata(path), error_prefix);
+
+    #[cfg(unix)]
+    if let Some(parent) = path.parent()
+        && let Ok(parent_metadata) = fs::metadata(parent)
+    {
+        make_writable(parent, &parent_metadata);
+    }
+
+    if metadata.is_dir() {
+        make_writable(path, &metadata);
+        // called before the walk descends, so nested read-only directories are readable
+        walk_inner(
+            path,
+            |p, m| {
+                if !m.is_symlink() {
+                    make_writable(p, m);
+                }
+            },
+            |_, _| true,
+            BogLevel::DEBUG,
+        );
+        get_or_err!(fs::remove_dir_all(path), error_prefix);
+    } else {
+        if !metadata.is_symlink() {
+            make_writable(path, &metadata);
+        }
+        get_or_err!(fs::remove_file(path), error_prefix);
+    }
+    true
+}
+
+/// Move path to the platform trash (the freedesktop trash on linux, Finder on macOS, the recycle bin on windows).
+/// Nothing is removed if that fails or isn't supported, returns false (after warning).
+#[cfg(feature = "trash")]
+pub fn trash(path: impl AsRef<Path>) -> bool {
+    let path = path.as_ref();
+    match trash::delete(path) {
+        Ok(()) => true,
+        Err(e) => {
+            wbog!("Failed to move {path:?} to trash: {e}");
+            false
+        }
+    }
+}
+
+/// Grant write access, logging failures at DEBUG since the removal will report them
+fn make_writable(path: &Path, metadata: &Metadata) {
+    let mut perms = metadata.permissions();
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        // directories also need read and search to remove their contents
+        let bits = if metadata.is_dir() { 0o700 } else { 0o200 };
+        if perms.mode() & bits == bits {
+            return;
+        }
+        perms.set_mode(perms.mode() | bits);
+    }
+    #[cfg(not(unix))]
+    {
+        if !perms.readonly() {
+            return;
+        }
+        #[allow(clippy::permissions_set_readonly_false)]
+        perms.set_readonly(false);
+    }
+
+    if let Err(e) = fs::set_permissions(path, perms) {
+        dbog!("Failed to make {path:?} writable: {e}");
+    }
+}
+
+/// Recursively copy the contents of src into dst, creating dst as needed.
+/// Entries (at any depth) for which filter returns false are skipped.
+/// Symlinks are copied as symlinks, permissions are preserved.
+/// Errors are logged and copying continues, returns false if anything failed.
+pub fn copy_dir_all(
+    src: impl AsRef<Path>,
+    dst: impl AsRef<Path>,
+    filter: impl Fn(&DirEntry) -> bool,
+) -> bool {
+    let src = src.as_ref();
+    let dst = dst.as_ref();
+    if same_existing_file(src, dst) || is_within(dst, src) {
+        ebog!("Failed to copy directory {src:?} to {dst:?}: the destination is inside the source");
+        return false;
+    }
+    copy_dir_inner(src, dst, &filter)
+}
+
+fn copy_dir_inner(src: &Path, dst: &Path, filter: &dyn Fn(&DirEntry) -> bool) -> bool {
+    let error_prefix = format!("Failed to copy directory {src:?} to {dst:?}");
+
+    get_or_err!(fs::create_dir_all(dst), error_prefix);
+    let entries = get_or_err!(fs::read_dir(src), error_prefix);
+
+    let mut ok = true;
+    for entry in entries {
+        let Some(entry) = entry.prefix_err(&error_prefix).or_err() else {
+            ok = false;
+            continue;
+        };
+        if !filter(&entry) {
+            continue;
+        }
+        let from = entry.path();
+        let to = dst.join(entry.file_name());
+
+        ok &= match entry.file_type() {
+            Ok(t) if t.is_dir() => copy_dir_inner(&from, &to, filter),
+            Ok(t) if t.is_symlink() => copy_symlink(&from, &to),
+            Ok(_) => copy_file(&from, &to),
+            Err(e) => {
+                ebog!("Failed to check type of {from:?}: {e}");
+                false
+            }
+        };
+    }
+
+    // after the contents, in case src is read-only
+    let perms = get_or_err!(fs::metadata(src), error_prefix).permissions();
+    ok &= fs::set_permissions(dst, perms)
+        .prefix_err(&error_prefix)
+        .or_err()
+        .is_some();
+    ok
+}
+
+fn copy_file(src: &Path, dst: &Path) -> bool {
+    let error_prefix = format!("Failed to copy {src:?} to {dst:?}");
+    get_or_err!(fs::copy(src, dst), error_prefix);
+    true
+}
+
+fn copy_symlink(src: &Path, dst: &Path) -> bool {
+    let error_prefix = format!("Failed to copy symlink {src:?} to {dst:?}");
+    let target = get_or_err!(fs::read_link(src), error_prefix);
+    symlink(target, dst)
+}
+
+/// Depth-first traversal calling f on every entry below dir (not dir itself).
+/// Metadata is from [`fs::symlink_metadata`]: symlinks are visited but never followed.
+/// Directories which can't be read are skipped with a warning.
+pub fn walk(dir: impl AsRef<Path>, f: impl FnMut(&Path, &Metadata)) {
+    walk_filtered(dir, f, |_, _| true)
+}
+
+/// [`walk`], descending only into directories for which descend returns true.
+/// f is called on a directory before descend.
+pub fn walk_filtered(
+    dir: impl AsRef<Path>,
+    f: impl FnMut(&Path, &Metadata),
+    descend: impl FnMut(&Path, &Metadata) -> bool,
+) {
+    walk_inner(dir.as_ref(), f, descend, BogLevel::WARN)
+}
+
+/// Traversal of [`walk_filtered`], logging failures at level
+fn walk_inner(
+    dir: &Path,
+    mut f: impl FnMut(&Path, &Metadata),
+    mut descend: impl FnMut(&Path, &Metadata) -> bool,
+    level: BogLevel,
+) {
+    // explicit stack so deep trees can't overflow
+    let mut stack = vec![dir.to_path_buf()];
+
+    while let Some(dir) = stack.pop() {
+        let entries = match fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(e) => {
+                bog(level, "", &format!("Failed to read directory {dir:?}: {e}"));
+                continue;
+            }
+        };
+
+        let mut subdirs = vec![];
+        for entry in entries {
+            let path = match entry {
+                Ok(entry) => entry.path(),
+                Err(e) => {
+                    bog(level, "", &format!("Failed to read entry in {dir:?}: {e}"));
+                    continue;
+                }
+            };
+            let meta = match fs::symlink_metadata(&path) {
+                Ok(meta) => meta,
+                Err(e) => {
+                    bog(
+                        level,
+                        "",
+                        &format!("Failed to check metadata of {path:?}: {e}"),
+                    );
+                    continue;
+                }
+            };
+
+            f(&path, &meta);
+            if meta.is_dir() && descend(&path, &meta) {
+                subdirs.push(path);
+            }
+        }
+        // reversed so the first subdirectory is visited first
+        stack.extend(subdirs.into_iter().rev());
+    }
+}
+
+/// Total size in bytes of the regular files below dir.
+/// Symlinks are not followed or counted, unreadable entries are skipped (logged at DEBUG).
+/// Returns 0 (after a warning) if dir does not exist.
+pub fn dir_size(dir: impl AsRef<Path>) -> u64 {
+    let mut size = 0;
+    walk_files(dir.as_ref(), |meta| size += meta.len());
+    size
+}
+
+/// Number of regular files below dir, see [`dir_size`].
+pub fn dir_file_count(dir: impl AsRef<Path>) -> u64 {
+    let mut count = 0;
+    walk_files(dir.as_ref(), |_| count += 1);
+    count
+}
+
+fn walk_files(dir: &Path, mut f: impl FnMut(&Metadata)) {
+    if !dir.exists() {
+        wbog!("{dir:?} does not exist");
+        return;
+    }
+    walk_inner(
+        dir,
+        |_, meta| {
+            if meta.is_file() {
+                f(meta)
+            }
+        },
+        |_, _| true,
+        BogLevel::DEBUG,
+    );
+}
+
+/// Move (rename) src to dst, falling back to copy-then-delete when they are on different filesystems.
+/// Permissions are preserved by the fallback.
+pub fn move_path(src: impl AsRef<Path>, dst: impl AsRef<Path>) -> bool {
+    let src = src.as_ref();
+    let dst = dst.as_ref();
+    let error_prefix = format!("Failed to move {src:?} to {dst:?}");
+
+    // renaming a file onto (a hardlink of) itself does nothing
+    if same_existing_file(src, dst) {
+        ebog!("{error_prefix}: they are the same file");
+        return false;
+    }
+    if fs::symlink_metadata(src).is_ok_and(|m| m.is_dir()) && is_within(dst, src) {
+        ebog!("{error_prefix}: the destination is inside the source");
+        return false;
+    }
+
+    match fs::rename(src, dst) {
+        Ok(()) => return true,
+        Err(e) if e.kind() == std::io::ErrorKind::CrossesDevices => {
+            dbog!("Moving {src:?} to {dst:?} across devices: copying instead");
+        }
+        Err(e) => {
+            ebog!("{error_prefix}: {e}");
+            return false;
+        }
+    }
+
+    let file_type = get_or_err!(fs::symlink_metadata(src), error_prefix).file_type();
+    if file_type.is_dir() {
+        // leave src in place if anything failed to copy
+        if !copy_dir_all(src, dst, |_| true) {
+            return false;
+        }
+        get_or_err!(fs::remove_dir_all(src), error_prefix);
+    } else {
+        let copied = if file_type.is_symlink() {
+            copy_symlink(src, dst)
+        } else {
+            copy_file(src, dst)
+        };
+        if !copied {
+            return false;
+        }
+        get_or_err!(fs::remove_file(src), error_prefix);
+    }
+    true
+}
+
 #[easy_ext::ext(FsPathExt)]
 pub impl<T: AsRef<Path>> T {
+    /// False if path is not an empty directory or can't be read (logged at DEBUG), see [`FsPathExt::try_is_empty_dir`].
     fn is_empty_dir(&self) -> bool {
         let path = self.as_ref();
-        fs::read_dir(path)
-            .map(|mut entries| entries.next().is_none())
-            .unwrap_or(false)
+        self.try_is_empty_dir().unwrap_or_else(|e| {
+            dbog!("Failed to read directory {path:?}: {e}");
+            false
+        })
+    }
+
+    /// Whether path is an empty directory, or the error from reading it.
+    fn try_is_empty_dir(&self) -> std::io::Result<bool> {
+        fs::read_dir(self.as_ref()).map(|mut entries| entries.next().is_none())
     }
 }
 
 //
-pub fn sort_by_mtime(paths: &mut Vec<PathBuf>) {
-    paths.sort_by(|a, b| {
-        let ma = fs::metadata(a).and_then(|m| m.modified());
-        let mb = fs::metadata(b).and_then(|m| m.modified());
-        match (ma, mb) {
-            (Ok(a), Ok(b)) => a.cmp(&b),
-            (Ok(_), Err(_)) => Ordering::Less,
-            (Err(_), Ok(_)) => Ordering::Greater,
-            (Err(_), Err(_)) => Ordering::Equal,
-        }
+/// Sort paths by modification time, oldest first unless descending.
+/// Paths whose metadata can't be read sort last in either direction; their count is returned.
+#[allow(clippy::ptr_arg)]
+pub fn sort_by_mtime(paths: &mut Vec<PathBuf>, descending: bool) -> usize {
+    sort_by_metadata(paths, descending, |m| m.modified())
+}
+
+/// Sort paths by size in bytes, smallest first unless descending, see [`sort_by_mtime`].
+pub fn sort_by_size(paths: &mut [PathBuf], descending: bool) -> usize {
+    sort_by_metadata(paths, descending, |m| Ok(m.len()))
+}
+
+fn sort_by_metadata<K: Ord>(
+    paths: &mut [PathBuf],
+    descending: bool,
+    key: impl Fn(&Metadata) -> std::io::Result<K>,
+) -> usize {
+    // fetch each key once
+    let mut keyed: Vec<_> = paths
+        .iter()
+        .map(|p| (fs::metadata(p).and_then(|m| key(&m)).ok(), p.clone()))
+        .collect();
+    let errors = keyed.iter().filter(|(k, _)| k.is_none()).count();
+
+    keyed.sort_by(|(a, _), (b, _)| match (a, b) {
+        (Some(a), Some(b)) if descending => b.cmp(a),
+        (Some(a), Some(b)) => a.cmp(b),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
     });
+
+    for (slot, (_, path)) in paths.iter_mut().zip(keyed) {
+        *slot = path;
+    }
+    errors
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("cba_bs_{name}_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn copy_dir_all_recurses() {
+        let dir = test_dir("copy");
+        let src = dir.join("src");
+        fs::create_dir_all(src.join("sub/deeper")).unwrap();
+        fs::write(src.join("a.txt"), "a").unwrap();
+        fs::write(src.join("skip.log"), "x").unwrap();
+        fs::write(src.join("sub/deeper/b.txt"), "b").unwrap();
+        std::os::unix::fs::symlink("a.txt", src.join("link")).unwrap();
+
+        let dst = dir.join("dst");
+        assert!(copy_dir_all(&src, &dst, |e| {
+            e.path().extension().is_none_or(|ext| ext != "log")
+        }));
+
+        assert_eq!(fs::read_to_string(dst.join("a.txt")).unwrap(), "a");
+        assert_eq!(
+            fs::read_to_string(dst.join("sub/deeper/b.txt")).unwrap(),
+            "b"
+        );
+        assert!(!dst.join("skip.log").exists());
+        assert_eq!(fs::read_link(dst.join("link")).unwrap(), Path::new("a.txt"));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn copy_and_move_into_self_fail() {
+        let dir = test_dir("into_self");
+        let src = dir.join("src");
+        fs::create_dir_all(&src).unwrap();
+        fs::write(src.join("f"), "f").unwrap();
+
+        assert!(!copy_dir_all(&src, &src, |_| true));
+        assert!(!copy_dir_all(&src, src.join("sub/deeper"), |_| true));
+        assert!(!copy_dir_all(&src, src.join("./sub/../sub"), |_| true));
+        assert!(!src.join("sub").exists());
+        assert!(!move_path(&src, src.join("sub")));
+        assert!(src.join("f").exists());
+
+        // a sibling sharing the prefix is fine
+        assert!(copy_dir_all(&src, dir.join("src2"), |_| true));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn walk_skips_symlinked_dirs() {
+        let dir = test_dir("walk");
+        fs::create_dir_all(dir.join("a/b")).unwrap();
+        fs::create_dir_all(dir.join("skip")).unwrap();
+        fs::write(dir.join("a/b/f.txt"), "").unwrap();
+        fs::write(dir.join("skip/g.txt"), "").unwrap();
+        // loop back to the root
+        std::os::unix::fs::symlink(&dir, dir.join("a/loop")).unwrap();
+
+        let rel = |p: &Path| p.strip_prefix(&dir).unwrap().to_path_buf();
+
+        let mut seen = vec![];
+        walk(&dir, |p, _| seen.push(rel(p)));
+        seen.sort();
+        let expected: Vec<PathBuf> = ["a", "a/b", "a/b/f.txt", "a/loop", "skip", "skip/g.txt"]
+            .iter()
+            .map(PathBuf::from)
+            .collect();
+        assert_eq!(seen, expected);
+
+        let mut seen = vec![];
+        walk_filtered(
+            &dir,
+            |p, m| {
+                if m.is_file() {
+                    seen.push(rel(p))
+                }
+            },
+            |p, _| !p.ends_with("skip"),
+        );
+        assert_eq!(seen, [PathBuf::from("a/b/f.txt")]);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn dir_size_counts_files_once() {
+        let dir = test_dir("size");
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        fs::write(dir.join("a"), "12345").unwrap();
+        fs::write(dir.join("sub/b"), "123").unwrap();
+        std::os::unix::fs::symlink(dir.join("a"), dir.join("sub/link")).unwrap();
+
+        assert_eq!(dir_size(&dir), 8);
+        assert_eq!(dir_file_count(&dir), 2);
+        assert_eq!(dir_size(dir.join("missing")), 0);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn clear_directory_modes() {
+        let dir = test_dir("clear");
+        fs::create_dir_all(dir.join("full/nested")).unwrap();
+        fs::write(dir.join("full/nested/f"), "").unwrap();
+        fs::write(dir.join("keep"), "").unwrap();
+        fs::write(dir.join("remove"), "").unwrap();
+
+        let not_keep = |e: &DirEntry| e.file_name() != "keep";
+        // full is not empty
+        assert!(!clear_directory(&dir, not_keep));
+        assert!(dir.join("full").exists());
+
+        assert!(clear_directory_recursive(&dir, not_keep));
+        assert!(!dir.join("full").exists());
+        assert!(!dir.join("remove").exists());
+        assert!(dir.join("keep").exists());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn sort_by_size_and_mtime() {
+        let dir = test_dir("sort");
+        let [small, big, missing] = ["small", "big", "missing"].map(|n| dir.join(n));
+        fs::write(&big, "123456").unwrap();
+        fs::write(&small, "1").unwrap();
+
+        let mut paths = vec![missing.clone(), big.clone(), small.clone()];
+        assert_eq!(sort_by_size(&mut paths, false), 1);
+        assert_eq!(paths, [small.clone(), big.clone(), missing.clone()]);
+        assert_eq!(sort_by_size(&mut paths, true), 1);
+        assert_eq!(paths, [big.clone(), small.clone(), missing.clone()]);
+
+        let old = fs::File::options().write(true).open(&big).unwrap();
+        old.set_modified(std::time::SystemTime::UNIX_EPOCH).unwrap();
+        assert_eq!(sort_by_mtime(&mut paths, true), 1);
+        assert_eq!(paths, [small, big, missing]);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn move_path_renames() {
+        let dir = test_dir("move");
+        fs::create_dir_all(dir.join("a/sub")).unwrap();
+        fs::write(dir.join("a/sub/f"), "f").unwrap();
+
+        assert!(move_path(dir.join("a"), dir.join("b")));
+        assert!(!dir.join("a").exists());
+        assert_eq!(fs::read_to_string(dir.join("b/sub/f")).unwrap(), "f");
+
+        assert!(!move_path(dir.join("missing"), dir.join("c")));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn set_mode_and_make_private() {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = |p: &Path| fs::metadata(p).unwrap().permissions().mode() & 0o777;
+
+        let dir = test_dir("mode");
+        let file = dir.join("secret");
+        fs::write(&file, "").unwrap();
+
+        assert!(set_mode(&file, 0o644));
+        assert_eq!(mode(&file), 0o644);
+        assert!(make_private(&file));
+        assert_eq!(mode(&file), 0o600);
+        assert!(make_private(&dir));
+        assert_eq!(mode(&dir), 0o700);
+        assert!(!make_private(dir.join("missing")));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn force_remove_read_only() {
+        let dir = test_dir("force");
+        fs::create_dir_all(dir.join("ro/nested")).unwrap();
+        fs::write(dir.join("ro/nested/f"), "").unwrap();
+        fs::write(dir.join("keep"), "").unwrap();
+        assert!(set_mode(dir.join("ro/nested"), 0o500));
+        assert!(set_mode(dir.join("ro"), 0o500));
+
+        assert!(clear_directory_force(&dir, |e| e.file_name() != "keep"));
+        assert!(!dir.join("ro").exists());
+        assert!(dir.join("keep").exists());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn hardlinks() {
+        let dir = test_dir("hardlink");
+        let [a, b, c] = ["a", "b", "c"].map(|n| dir.join(n));
+        fs::write(&a, "x").unwrap();
+        fs::write(&c, "x").unwrap();
+
+        assert!(hardlink(&a, &b));
+        assert!(is_hardlink_of(&a, &b));
+        assert!(!is_hardlink_of(&a, &c));
+        assert!(!is_hardlink_of(&a, dir.join("missing")));
+        std::os::unix::fs::symlink(&a, dir.join("sym")).unwrap();
+        assert!(!is_hardlink_of(&a, dir.join("sym")));
+        assert!(is_same_file(&a, dir.join("sym")));
+        assert!(is_same_file(&a, &b));
+        assert!(is_same_file(&dir, dir.join(".")));
+        assert!(!is_same_file(&a, &c));
+        assert!(!is_same_file(&a, dir.join("missing")));
+        assert!(!move_path(&a, &b));
+        assert!(a.exists());
+        assert!(!hardlink(&a, &b)); // exists
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn try_is_empty_dir_cases() {
+        let dir = test_dir("empty");
+        let file = dir.join("file");
+
+        assert!(dir.try_is_empty_dir().unwrap());
+        assert!(dir.is_empty_dir());
+
+        fs::write(&file, "").unwrap();
+        assert!(!dir.try_is_empty_dir().unwrap());
+        assert!(file.try_is_empty_dir().is_err());
+        assert!(!file.is_empty_dir());
+
+        let missing = dir.join("missing");
+        assert_eq!(
+            missing.try_is_empty_dir().unwrap_err().kind(),
+            std::io::ErrorKind::NotFound
+        );
+        assert!(!missing.is_empty_dir());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn create_dir_secure_mode() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = test_dir("secure");
+        let nested = dir.join("a/b");
+
+        assert!(create_dir_secure(&nested));
+        for p in [dir.join("a"), nested.clone()] {
+            assert_eq!(fs::metadata(p).unwrap().permissions().mode() & 0o777, 0o700);
+        }
+        assert!(create_dir_secure(&nested));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn type_checks_nofollow() {
+        let dir = test_dir("types");
+        let file = dir.join("file");
+        fs::write(&file, "").unwrap();
+        std::os::unix::fs::symlink(&file, dir.join("link")).unwrap();
+        std::os::unix::fs::symlink(dir.join("missing"), dir.join("dangling")).unwrap();
+
+        assert!(is_regular_file(&file));
+        assert!(!is_regular_file(dir.join("link")));
+        assert!(is_directory(&dir));
+        assert!(!is_directory(&file));
+        assert!(exists_nofollow(dir.join("dangling")));
+        assert!(!exists_nofollow(dir.join("missing")));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
 }
@@ -1,66 +1,82 @@
 // BOG
 
+/// Log the error at level and return `Default::default()` (or `Err(Default::default())` with `?`, or `Err($return)`)
 #[macro_export]
-macro_rules! get_or_err {
-    ($expr:expr, $bog_prefix:expr) => {
+macro_rules! get_or_bog {
+    ($expr:expr, $level:expr, $bog_prefix:expr) => {
         match $expr {
             Ok(v) => v,
             Err(e) => {
-                $crate::ebog!("{}: {e}", $bog_prefix);
+                $crate::bog::bog($level, "", &format!("{}: {e}", $bog_prefix));
                 return Default::default();
             }
         }
     };
 
-    ($expr:expr, $bog_prefix:expr, ?) => {
+    ($expr:expr, $level:expr, $bog_prefix:expr, ?) => {
         match $expr {
             Ok(v) => v,
             Err(e) => {
-                $crate::ebog!("{}: {e}", $bog_prefix);
+                $crate::bog::bog($level, "", &format!("{}: {e}", $bog_prefix));
                 return Err(Default::default());
             }
         }
     };
 
     // Err($return) because this is only intended to be used in a fn returning Result/Option/bool
-    ($expr:expr, $bog_prefix:expr, $return:expr) => {
+    ($expr:expr, $level:expr, $bog_prefix:expr, $return:expr) => {
         match $expr {
             Ok(v) => v,
             Err(e) => {
-                $crate::ebog!("{}: {e}", $bog_prefix);
+                $crate::bog::bog($level, "", &format!("{}: {e}", $bog_prefix));
                 return Err($return);
             }
         }
     };
 }
 
+#[macro_export]
+macro_rules! get_or_err {
+    ($expr:expr, $bog_prefix:expr $(, $($rest:tt)+)?) => {
+        $crate::get_or_bog!($expr, $crate::bog::BogLevel::ERROR, $bog_prefix $(, $($rest)+)?)
+    };
+}
+
 #[macro_export]
 macro_rules! get_or_warn {
-    ($expr:expr, $bog_prefix:expr) => {
+    ($expr:expr, $bog_prefix:expr $(, $($rest:tt)+)?) => {
+        $crate::get_or_bog!($expr, $crate::bog::BogLevel::WARN, $bog_prefix $(, $($rest)+)?)
+    };
+}
+
+/// [`get_or_bog`] for Option
+#[macro_export]
+macro_rules! unwrap_or_bog {
+    ($expr:expr, $level:expr, $bog_err:expr) => {
         match $expr {
-            Ok(v) => v,
-            Err(e) => {
-                $crate::wbog!("{}: {e}", $bog_prefix);
+            Some(v) => v,
+            None => {
+                $crate::bog::bog($level, "", &format!("{}", $bog_err));
                 return Default::default();
             }
         }
     };
 
-    ($expr:expr, $bog_prefix:expr, ?) => {
+    ($expr:expr, $level:expr, $bog_err:expr, ?) => {
         match $expr {
-            Ok(v) => v,
-            Err(e) => {
-                $crate::wbog!("{}: {e}", $bog_prefix);
+            Some(v) => v,
+            None => {
+                $crate::bog::bog($level, "", &format!("{}", $bog_err));
                 return Err(Default::default());
             }
         }
     };
 
-    ($expr:expr, $bog_prefix:expr, $return:expr) => {
+    ($expr:expr, $level:expr, $bog_err:expr, $return:expr) => {
         match $expr {
-            Ok(v) => v,
-            Err(e) => {
-                $crate::wbog!("{}: {e}", $bog_prefix);
+            Some(v) => v,
+            None => {
+                $crate::bog::bog($level, "", &format!("{}", $bog_err));
                 return Err($return);
             }
         }
@@ -69,66 +85,107 @@ macro_rules! get_or_warn {
 
 #[macro_export]
 macro_rules! unwrap_or_err {
-    ($expr:expr, $bog_err:expr) => {
+    ($expr:expr, $bog_err:expr $(, $($rest:tt)+)?) => {
+        $crate::unwrap_or_bog!($expr, $crate::bog::BogLevel::ERROR, $bog_err $(, $($rest)+)?)
+    };
+}
+
+#[macro_export]
+macro_rules! unwrap_or_warn {
+    ($expr:expr, $bog_err:expr $(, $($rest:tt)+)?) => {
+        $crate::unwrap_or_bog!($expr, $crate::bog::BogLevel::WARN, $bog_err $(, $($rest)+)?)
+    };
+}
+
+/// [`get_or_err`], but `continue` the enclosing (or labeled) loop instead of returning
+#[macro_export]
+macro_rules! get_or_continue {
+    ($expr:expr, $bog_prefix:expr) => {
         match $expr {
-            Some(v) => v,
-            None => {
-                $crate::ebog!("{}", $bog_err);
-                return Default::default();
+            Ok(v) => v,
+            Err(e) => {
+                $crate::ebog!("{}: {e}", $bog_prefix);
+                continue;
             }
         }
     };
 
-    ($expr:expr, $bog_err:expr, ?) => {
+    ($expr:expr, $bog_prefix:expr, $label:lifetime) => {
+        match $expr {
+            Ok(v) => v,
+            Err(e) => {
+                $crate::ebog!("{}: {e}", $bog_prefix);
+                continue $label;
+            }
+        }
+    };
+}
+
+/// [`unwrap_or_err`], but `continue` the enclosing (or labeled) loop instead of returning
+#[macro_export]
+macro_rules! unwrap_or_continue {
+    ($expr:expr, $bog_err:expr) => {
         match $expr {
             Some(v) => v,
             None => {
                 $crate::ebog!("{}", $bog_err);
-                return Err(Default::default());
+                continue;
             }
         }
     };
 
-    ($expr:expr, $bog_err:expr, $return:expr) => {
+    ($expr:expr, $bog_err:expr, $label:lifetime) => {
         match $expr {
             Some(v) => v,
             None => {
                 $crate::ebog!("{}", $bog_err);
-                return Err($return);
+                continue $label;
             }
         }
     };
 }
 
+/// Log the error and `break` out of the enclosing (or labeled) loop.
+/// Unlike [`get_or_err`], this does not return from the function.
 #[macro_export]
-macro_rules! unwrap_or_warn {
-    ($expr:expr, $bog_err:expr) => {
+macro_rules! get_or_break {
+    ($expr:expr, $bog_prefix:expr) => {
         match $expr {
-            Some(v) => v,
-            None => {
-                $crate::wbog!("{}", $bog_err);
-                return Default::default();
+            Ok(v) => v,
+            Err(e) => {
+                $crate::ebog!("{}: {e}", $bog_prefix);
+                break;
             }
         }
     };
 
-    ($expr:expr, $bog_err:expr, ?) => {
+    ($expr:expr, $bog_prefix:expr, $label:lifetime) => {
         match $expr {
-            Some(v) => v,
-            None => {
-                $crate::wbog!("{}", $bog_err);
-                return Err(Default::default());
+            Ok(v) => v,
+            Err(e) => {
+                $crate::ebog!("{}: {e}", $bog_prefix);
+                break $label;
             }
         }
     };
+}
 
-    ($expr:expr, $bog_err:expr, $return:expr) => {
-        match $expr {
-            Some(v) => v,
-            None => {
-                $crate::wbog!("{}", $bog_err);
-                return Err($return);
-            }
+/// Bog the formatted message at level and return it as `Err(String)`, like anyhow's `bail!`
+#[macro_export]
+macro_rules! bog_bail {
+    ($level:expr, $($arg:tt)+) => {{
+        let msg = format!($($arg)+);
+        $crate::bog::bog($level, "", &msg);
+        return Err(msg.into());
+    }};
+}
+
+/// [`bog_bail`] if cond is false
+#[macro_export]
+macro_rules! bog_ensure {
+    ($cond:expr, $level:expr, $($arg:tt)+) => {
+        if !$cond {
+            $crate::bog_bail!($level, $($arg)+);
         }
     };
 }
@@ -167,6 +224,20 @@ macro_rules! _dbg {
     }};
 }
 
+/// Evaluate expr, bogging its elapsed time at DEBUG tagged with the expression (or label), and return its value
+#[macro_export]
+macro_rules! time {
+    ($label:expr; $expr:expr) => {{
+        let start = std::time::Instant::now();
+        let value = $expr;
+        $crate::dbog!("{}", $label; "{}", $crate::misc::human_duration(start.elapsed()));
+        value
+    }};
+    ($expr:expr) => {
+        $crate::time!(stringify!($expr); $expr)
+    };
+}
+
 /// Prints to stderr like `eprintln!` but only in debug builds
 #[macro_export]
 macro_rules! _eprint {
@@ -177,3 +248,140 @@ macro_rules! _eprint {
         }
     };
 }
+
+/// [`dbog`] but only in debug builds
+#[macro_export]
+macro_rules! _dbog {
+    ($($args:tt)*) => {{
+        #[cfg(debug_assertions)]
+        $crate::dbog!($($args)*);
+    }};
+}
+
+/// [`ibog`] but only in debug builds
+#[macro_export]
+macro_rules! _ibog {
+    ($($args:tt)*) => {{
+        #[cfg(debug_assertions)]
+        $crate::ibog!($($args)*);
+    }};
+}
+
+/// [`wbog`] but only in debug builds
+#[macro_export]
+macro_rules! _wbog {
+    ($($args:tt)*) => {{
+        #[cfg(debug_assertions)]
+        $crate::wbog!($($args)*);
+    }};
+}
+
+/// [`ebog`] but only in debug builds
+#[macro_export]
+macro_rules! _ebog {
+    ($($args:tt)*) => {{
+        #[cfg(debug_assertions)]
+        $crate::ebog!($($args)*);
+    }};
+}
+
+/// [`nbog`] but only in debug builds
+#[macro_export]
+macro_rules! _nbog {
+    ($($args:tt)*) => {{
+        #[cfg(debug_assertions)]
+        $crate::nbog!($($args)*);
+    }};
+}
+
+#[cfg(test)]
+mod test {
+    #[test]
+    fn continue_macros() {
+        let mut seen = vec![];
+        for s in ["1", "x", "3"] {
+            let n: u32 = get_or_continue!(s.parse::<u32>(), "Invalid number");
+            seen.push(n);
+        }
+        assert_eq!(seen, [1, 3]);
+
+        let mut seen = vec![];
+        'outer: for row in [vec![Some(1), Some(2)], vec![None, Some(4)], vec![Some(5)]] {
+            for x in row {
+                let x = unwrap_or_continue!(x, "Missing value", 'outer);
+                seen.push(x);
+            }
+        }
+        assert_eq!(seen, [1, 2, 5]);
+    }
+
+    #[test]
+    fn break_macro() {
+        let mut seen = vec![];
+        for s in ["1", "x", "3"] {
+            seen.push(get_or_break!(s.parse::<u32>(), "Invalid number"));
+        }
+        assert_eq!(seen, [1]);
+
+        let mut seen = vec![];
+        'outer: for row in [["1", "2"], ["x", "4"], ["5", "6"]] {
+            for s in row {
+                seen.push(get_or_break!(s.parse::<u32>(), "Invalid number", 'outer));
+            }
+        }
+        assert_eq!(seen, [1, 2]);
+    }
+
+    #[test]
+    fn bog_level_macros() {
+        use crate::bog::BogLevel;
+
+        fn parse(s: &str) -> Option<u32> {
+            Some(get_or_bog!(s.parse::<u32>(), BogLevel::INFO, "Invalid number"))
+        }
+        fn parse_err(s: &str) -> Result<u32, String> {
+            Ok(get_or_err!(s.parse::<u32>(), "Invalid number", ?))
+        }
+        fn first(v: &[u32]) -> Result<u32, u32> {
+            Ok(unwrap_or_warn!(v.first().copied(), "Empty", 0))
+        }
+
+        assert_eq!(parse("1"), Some(1));
+        assert_eq!(parse("x"), None);
+        assert_eq!(parse_err("x"), Err(String::new()));
+        assert_eq!(first(&[]), Err(0));
+        assert_eq!(first(&[3]), Ok(3));
+    }
+
+    #[test]
+    fn bail_macros() {
+        use crate::bog::BogLevel;
+
+        fn half(n: u32) -> Result<u32, String> {
+            bog_ensure!(n.is_multiple_of(2), BogLevel::WARN, "{n} is odd");
+            if n == 0 {
+                bog_bail!(BogLevel::ERROR, "zero");
+            }
+            Ok(n / 2)
+        }
+
+        assert_eq!(half(4), Ok(2));
+        assert_eq!(half(3), Err("3 is odd".into()));
+        assert_eq!(half(0), Err("zero".into()));
+    }
+
+    #[test]
+    fn time_returns_value() {
+        assert_eq!(time!(1 + 1), 2);
+        assert_eq!(time!("sum"; (1..=4).sum::<u32>()), 10);
+    }
+
+    #[test]
+    fn debug_only_bogs_compile() {
+        _dbog!("x = {}", 1);
+        _ibog!("tag"; "x = {}", 1);
+        _wbog!("x = {}", 1);
+        _ebog!("x = {}", 1);
+        _nbog!("x = {}", 1);
+    }
+}
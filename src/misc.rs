@@ -17,6 +17,18 @@ where
             *self = v.clone();
         }
     }
+
+    /// Set self to the value of maybe, or to default() if None
+    fn maybe_or(&mut self, maybe: Option<T>, default: impl FnOnce() -> T) {
+        *self = maybe.unwrap_or_else(default);
+    }
+
+    /// Replace self with val if pred holds for the current value
+    fn replace_if(&mut self, pred: impl FnOnce(&T) -> bool, val: T) {
+        if pred(self) {
+            *self = val;
+        }
+    }
 }
 
 // this would be more useful if try blocks exposed their "other" type
@@ -39,21 +51,539 @@ pub impl<T, E> Result<T, E> {
             Err(e) => Err(format!("{prefix}: {e}")),
         }
     }
+
+    /// [`ResultExt::prefix_err`], but only build the prefix on Err
+    fn prefix_err_with(self, f: impl FnOnce() -> String) -> Result<T, String>
+    where
+        E: std::fmt::Display,
+    {
+        match self {
+            Ok(val) => Ok(val),
+            Err(e) => Err(format!("{}: {e}", f())),
+        }
+    }
+}
+
+#[easy_ext::ext(OptionExt)]
+pub impl<T> Option<T> {
+    /// Convert None to the string prefix, see [`ResultExt::prefix_err`]
+    fn ok_or_prefix(self, prefix: &str) -> Result<T, String> {
+        self.ok_or_else(|| prefix.to_string())
+    }
+
+    /// Bog msg at level if None, passing self through
+    fn ok_or_bog(self, level: crate::bog::BogLevel, msg: &str) -> Option<T> {
+        if self.is_none() {
+            crate::bog::bog(level, "", msg);
+        }
+        self
+    }
+}
+
+// ----------------- MERGE -----------------
+
+/// Layer values, e.g. config from defaults, then a file, then CLI flags.
+/// For structs, implement this with [`merge_fields`].
+pub trait Merge {
+    /// Overwrite self with the set parts of other
+    fn merge(&mut self, other: Self);
+
+    fn merged(mut self, other: Self) -> Self
+    where
+        Self: Sized,
+    {
+        self.merge(other);
+        self
+    }
+}
+
+/// Replaced by other if it is Some, see [`MaybeExt::maybe_take`]
+impl<T> Merge for Option<T> {
+    fn merge(&mut self, other: Self) {
+        if other.is_some() {
+            *self = other;
+        }
+    }
+}
+
+/// Extended by other
+impl<T> Merge for Vec<T> {
+    fn merge(&mut self, other: Self) {
+        self.extend(other);
+    }
+}
+
+/// Merge the listed fields of other into self
+///
+/// # Example
+/// ```rust
+/// use cli_boilerplate_automation::{merge_fields, misc::Merge};
+///
+/// #[derive(Default)]
+/// struct Config {
+///     name: Option<String>,
+///     paths: Vec<String>,
+/// }
+///
+/// impl Merge for Config {
+///     fn merge(&mut self, other: Self) {
+///         merge_fields!(self, other; name, paths);
+///     }
+/// }
+///
+/// let file = Config { name: Some("file".into()), paths: vec!["a".into()] };
+/// let cli = Config { name: None, paths: vec!["b".into()] };
+/// let config = Config::default().merged(file).merged(cli);
+/// assert_eq!(config.name.as_deref(), Some("file"));
+/// assert_eq!(config.paths, ["a", "b"]);
+/// ```
+#[macro_export]
+macro_rules! merge_fields {
+    ($self:expr, $other:expr; $($field:ident),+ $(,)?) => {{
+        let other = $other;
+        $($crate::misc::Merge::merge(&mut $self.$field, other.$field);)+
+    }};
+}
+
+// ----------------- FORMAT ----------------
+use crate::bog::BogLevel;
+use std::time::Duration;
+
+/// Format a byte count with binary units, e.g. `512 B`, `1.5 KiB`
+pub fn human_bytes(n: u64) -> String {
+    format_bytes(n, 1024, &["B", "KiB", "MiB", "GiB", "TiB", "PiB", "EiB"])
+}
+
+/// Format a byte count with decimal units, e.g. `512 B`, `1.5 KB`
+pub fn human_bytes_si(n: u64) -> String {
+    format_bytes(n, 1000, &["B", "KB", "MB", "GB", "TB", "PB", "EB"])
+}
+
+fn format_bytes(n: u64, base: u64, units: &[&str]) -> String {
+    if n < base {
+        return format!("{n} {}", units[0]);
+    }
+    let mut value = n as f64;
+    let mut unit = 0;
+    // also move up when rounding would display the base, e.g. 1024.0 KiB as 1.0 MiB
+    while value >= base as f64 - 0.05 && unit < units.len() - 1 {
+        value /= base as f64;
+        unit += 1;
+    }
+    format!("{value:.1} {}", units[unit])
+}
+
+/// Format a duration compactly in the largest fitting unit, e.g. `450ms`, `1.50s`, `2m3s`, `1h5m`
+pub fn human_duration(d: Duration) -> String {
+    let secs = d.as_secs();
+    if d < Duration::from_micros(1) {
+        format!("{}ns", d.as_nanos())
+    } else if d < Duration::from_millis(1) {
+        format!("{}µs", d.as_micros())
+    } else if d < Duration::from_secs(1) {
+        format!("{}ms", d.as_millis())
+    } else if secs < 60 {
+        format!("{:.2}s", d.as_secs_f64())
+    } else if secs < 3600 {
+        format!("{}m{}s", secs / 60, secs % 60)
+    } else {
+        format!("{}h{}m", secs / 3600, secs % 3600 / 60)
+    }
+}
+
+/// Format a duration as seconds with precision decimal places, preceded by minutes and hours when nonzero, e.g. `1h0m3.250s`
+pub fn human_duration_precise(d: Duration, precision: usize) -> String {
+    let secs = d.as_secs();
+    let rem = (secs % 60) as f64 + d.subsec_nanos() as f64 / 1e9;
+    if secs < 60 {
+        format!("{rem:.precision$}s")
+    } else if secs < 3600 {
+        format!("{}m{rem:.precision$}s", secs / 60)
+    } else {
+        format!("{}h{}m{rem:.precision$}s", secs / 3600, secs % 3600 / 60)
+    }
+}
+
+// ----------------- RETRY -----------------
+/// Call f until it succeeds, up to attempts times, returning the last error on exhaustion.
+/// The delay between attempts starts at backoff and doubles each time, with up to 50% random jitter added.
+/// Retries are logged at DEBUG.
+pub fn retry<T, E: std::fmt::Display>(
+    attempts: u32,
+    backoff: Duration,
+    f: impl FnMut() -> Result<T, E>,
+) -> Result<T, E> {
+    retry_if(attempts, backoff, |_| true, f)
+}
+
+/// [`retry`], logging retries at level instead of DEBUG.
+pub fn retry_with_level<T, E: std::fmt::Display>(
+    attempts: u32,
+    backoff: Duration,
+    level: BogLevel,
+    f: impl FnMut() -> Result<T, E>,
+) -> Result<T, E> {
+    retry_inner(attempts, backoff, level, |_| true, f)
+}
+
+/// [`retry`], returning immediately on errors for which should_retry is false.
+pub fn retry_if<T, E: std::fmt::Display>(
+    attempts: u32,
+    backoff: Duration,
+    should_retry: impl Fn(&E) -> bool,
+    f: impl FnMut() -> Result<T, E>,
+) -> Result<T, E> {
+    retry_inner(attempts, backoff, BogLevel::DEBUG, should_retry, f)
+}
+
+fn retry_inner<T, E: std::fmt::Display>(
+    attempts: u32,
+    backoff: Duration,
+    level: BogLevel,
+    should_retry: impl Fn(&E) -> bool,
+    mut f: impl FnMut() -> Result<T, E>,
+) -> Result<T, E> {
+    let attempts = attempts.max(1);
+    let mut delay = backoff;
+
+    let mut attempt = 1;
+    loop {
+        match f() {
+            Err(e) if attempt < attempts && should_retry(&e) => {
+                let sleep = delay.saturating_add(jitter(delay / 2));
+                crate::bog::bog(
+                    level,
+                    "",
+                    &format!("Attempt {attempt}/{attempts} failed: {e}, retrying in {sleep:?}"),
+                );
+                std::thread::sleep(sleep);
+                delay = delay.saturating_mul(2);
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Random duration up to max
+fn jitter(max: Duration) -> Duration {
+    use std::hash::{BuildHasher, RandomState};
+    // RandomState is randomly seeded, which is good enough here
+    let random = RandomState::new().hash_one(std::time::Instant::now());
+    max.mul_f64((random % 1024) as f64 / 1024.0)
+}
+
+// ------------------ ENV ------------------
+use std::{
+    ffi::{OsStr, OsString},
+    sync::{Mutex, MutexGuard, PoisonError},
+};
+
+static ENV_LOCK: Mutex<()> = Mutex::new(());
+
+/// Set environment variables, restoring their previous values (or unsetting them) on drop.
+///
+/// Guards are serialized by a global lock: constructing one blocks until any other is dropped,
+/// so don't hold two on the same thread. Code reading the environment without a guard can still race.
+///
+/// # Example
+/// ```rust
+/// use cli_boilerplate_automation::misc::EnvGuard;
+///
+/// {
+///     let mut env = EnvGuard::new([("CBA_EXAMPLE", "1")]);
+///     env.set("NO_COLOR", "1");
+///     assert_eq!(std::env::var("CBA_EXAMPLE").as_deref(), Ok("1"));
+/// }
+/// assert!(std::env::var_os("CBA_EXAMPLE").is_none());
+/// ```
+pub struct EnvGuard {
+    /// Original values, in order of first modification
+    saved: Vec<(OsString, Option<OsString>)>,
+    _lock: MutexGuard<'static, ()>,
+}
+
+impl EnvGuard {
+    pub fn new(vars: impl IntoIterator<Item = (impl AsRef<OsStr>, impl AsRef<OsStr>)>) -> Self {
+        let mut guard = Self {
+            saved: Vec::new(),
+            _lock: ENV_LOCK.lock().unwrap_or_else(PoisonError::into_inner),
+        };
+        for (key, val) in vars {
+            guard.set(key, val);
+        }
+        guard
+    }
+
+    pub fn set(&mut self, key: impl AsRef<OsStr>, val: impl AsRef<OsStr>) -> &mut Self {
+        self.save(key.as_ref());
+        // SAFETY: other guards are excluded by ENV_LOCK
+        unsafe { std::env::set_var(key, val) };
+        self
+    }
+
+    pub fn remove(&mut self, key: impl AsRef<OsStr>) -> &mut Self {
+        self.save(key.as_ref());
+        // SAFETY: see set
+        unsafe { std::env::remove_var(key) };
+        self
+    }
+
+    fn save(&mut self, key: &OsStr) {
+        if !self.saved.iter().any(|(k, _)| k == key) {
+            self.saved.push((key.to_owned(), std::env::var_os(key)));
+        }
+    }
+}
+
+impl Drop for EnvGuard {
+    fn drop(&mut self) {
+        for (key, val) in self.saved.drain(..).rev() {
+            // SAFETY: see set
+            unsafe {
+                match val {
+                    Some(val) => std::env::set_var(key, val),
+                    None => std::env::remove_var(key),
+                }
+            }
+        }
+    }
 }
 
 // -----------------------------------------
 use log::LevelFilter;
+
+/// The maximum level enabled by `RUST_LOG`, Info if unset or invalid, see [`log_filter_from_env`].
 pub fn level_filter_from_env() -> LevelFilter {
-    match std::env::var("RUST_LOG")
-        .ok()
-        .map(|s| s.to_lowercase())
-        .as_deref()
-    {
-        Some("trace") => LevelFilter::Trace,
-        Some("debug") => LevelFilter::Debug,
-        Some("info") => LevelFilter::Info,
-        Some("warn") => LevelFilter::Warn,
-        Some("error") => LevelFilter::Error,
-        _ => LevelFilter::Info,
-    }
-}
\ No newline at end of file
+    log_filter_from_env().max_level()
+}
+
+/// Parse `RUST_LOG` as comma-separated directives, each either a bare level or `module=level`.
+pub fn log_filter_from_env() -> EnvLogFilter {
+    EnvLogFilter::parse(&std::env::var("RUST_LOG").unwrap_or_default())
+}
+
+/// Directives parsed from a `RUST_LOG` style string, e.g. `warn,mycrate=debug`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EnvLogFilter {
+    /// The bare level, if given
+    pub default: Option<LevelFilter>,
+    /// (module, level) pairs in order of appearance
+    pub directives: Vec<(String, LevelFilter)>,
+}
+
+impl EnvLogFilter {
+    /// Invalid directives are ignored
+    pub fn parse(spec: &str) -> Self {
+        let mut filter = Self::default();
+        for directive in spec.split(',').map(str::trim).filter(|d| !d.is_empty()) {
+            match directive.split_once('=') {
+                Some((module, level)) => {
+                    if let Some(level) = parse_level(level) {
+                        filter.directives.push((module.trim().to_string(), level));
+                    }
+                }
+                // unlike env_logger, a bare word that isn't a level is ignored, not taken as a module
+                None => {
+                    if let Some(level) = parse_level(directive) {
+                        filter.default = Some(level);
+                    }
+                }
+            }
+        }
+        filter
+    }
+
+    /// The most verbose level of any directive, Info if there are none
+    pub fn max_level(&self) -> LevelFilter {
+        self.default
+            .into_iter()
+            .chain(self.directives.iter().map(|(_, level)| *level))
+            .max()
+            .unwrap_or(LevelFilter::Info)
+    }
+
+    /// The level for module (a `::` separated path), from the longest matching directive
+    pub fn level_for(&self, module: &str) -> LevelFilter {
+        self.directives
+            .iter()
+            .filter(|(name, _)| {
+                module == name
+                    || module
+                        .strip_prefix(name.as_str())
+                        .is_some_and(|rest| rest.starts_with("::"))
+            })
+            .max_by_key(|(name, _)| name.len())
+            .map(|(_, level)| *level)
+            .or(self.default)
+            .unwrap_or(LevelFilter::Info)
+    }
+}
+
+fn parse_level(s: &str) -> Option<LevelFilter> {
+    s.trim().parse().ok()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn env_guard_restores() {
+        let mut env = EnvGuard::new([("CBA_ENV_GUARD", "1"), ("CBA_ENV_GUARD_NEW", "1")]);
+        env.set("CBA_ENV_GUARD", "2").remove("CBA_ENV_GUARD_NEW");
+        assert_eq!(std::env::var("CBA_ENV_GUARD").as_deref(), Ok("2"));
+        assert!(std::env::var_os("CBA_ENV_GUARD_NEW").is_none());
+
+        drop(env);
+        assert!(std::env::var_os("CBA_ENV_GUARD").is_none());
+        assert!(std::env::var_os("CBA_ENV_GUARD_NEW").is_none());
+    }
+
+    #[test]
+    fn merge_option_and_vec() {
+        assert_eq!(Some(1).merged(None), Some(1));
+        assert_eq!(Some(1).merged(Some(2)), Some(2));
+        assert_eq!(None.merged(Some(2)), Some(2));
+        assert_eq!(vec![1].merged(vec![2, 3]), [1, 2, 3]);
+    }
+
+    #[test]
+    fn prefix_err_with_is_lazy() {
+        let ok: Result<u8, &str> = Ok(1);
+        assert_eq!(ok.prefix_err_with(|| unreachable!()), Ok(1));
+        let err: Result<u8, &str> = Err("boom");
+        assert_eq!(
+            err.prefix_err_with(|| "Failed".into()),
+            Err("Failed: boom".into())
+        );
+    }
+
+    #[test]
+    fn human_bytes_units() {
+        assert_eq!(human_bytes(0), "0 B");
+        assert_eq!(human_bytes(512), "512 B");
+        assert_eq!(human_bytes(1023), "1023 B");
+        assert_eq!(human_bytes(1024), "1.0 KiB");
+        assert_eq!(human_bytes(1536), "1.5 KiB");
+        assert_eq!(human_bytes(1024 * 1024 - 1), "1.0 MiB");
+        assert_eq!(human_bytes(5 * 1024 * 1024 * 1024), "5.0 GiB");
+        assert_eq!(human_bytes(u64::MAX), "16.0 EiB");
+
+        assert_eq!(human_bytes_si(999), "999 B");
+        assert_eq!(human_bytes_si(1500), "1.5 KB");
+        assert_eq!(human_bytes_si(2_000_000), "2.0 MB");
+        assert_eq!(human_bytes_si(u64::MAX), "18.4 EB");
+    }
+
+    #[test]
+    fn human_duration_units() {
+        assert_eq!(human_duration(Duration::ZERO), "0ns");
+        assert_eq!(human_duration(Duration::from_nanos(999)), "999ns");
+        assert_eq!(human_duration(Duration::from_micros(12)), "12µs");
+        assert_eq!(human_duration(Duration::from_millis(450)), "450ms");
+        assert_eq!(human_duration(Duration::from_millis(1500)), "1.50s");
+        assert_eq!(human_duration(Duration::from_secs(123)), "2m3s");
+        assert_eq!(human_duration(Duration::from_secs(3900)), "1h5m");
+
+        assert_eq!(
+            human_duration_precise(Duration::from_millis(1500), 3),
+            "1.500s"
+        );
+        assert_eq!(
+            human_duration_precise(Duration::from_millis(123_250), 1),
+            "2m3.2s"
+        );
+        assert_eq!(
+            human_duration_precise(Duration::from_millis(3_603_250), 2),
+            "1h0m3.25s"
+        );
+        assert_eq!(human_duration_precise(Duration::from_millis(1), 0), "0s");
+    }
+
+    #[test]
+    fn retry_attempts() {
+        let mut calls = 0;
+        let result: Result<u32, String> = retry(3, Duration::from_millis(1), || {
+            calls += 1;
+            if calls < 3 {
+                Err(format!("fail {calls}"))
+            } else {
+                Ok(calls)
+            }
+        });
+        assert_eq!(result, Ok(3));
+
+        let mut calls = 0;
+        let result: Result<(), String> = retry(2, Duration::ZERO, || {
+            calls += 1;
+            Err(format!("fail {calls}"))
+        });
+        assert_eq!(result, Err("fail 2".into()));
+
+        let mut calls = 0;
+        let result: Result<(), String> = retry_if(
+            5,
+            Duration::ZERO,
+            |e| e != "fatal",
+            || {
+                calls += 1;
+                Err("fatal".into())
+            },
+        );
+        assert_eq!((result, calls), (Err("fatal".into()), 1));
+    }
+
+    #[test]
+    fn maybe_combinators() {
+        let mut width = 80;
+        width.maybe_or(None, || 100);
+        assert_eq!(width, 100);
+        width.maybe_or(Some(120), || 100);
+        assert_eq!(width, 120);
+
+        width.replace_if(|w| *w > 200, 200);
+        assert_eq!(width, 120);
+        width.replace_if(|w| *w > 100, 100);
+        assert_eq!(width, 100);
+
+        // Option has an inherent take_if, which this must not clash with
+        let mut flag = Some(1);
+        flag.replace_if(Option::is_none, Some(2));
+        assert_eq!(flag, Some(1));
+        flag.replace_if(|f| *f == Some(1), None);
+        assert_eq!(flag, None);
+    }
+
+    #[test]
+    fn log_filter_directives() {
+        let filter = EnvLogFilter::parse("info");
+        assert_eq!(filter.default, Some(LevelFilter::Info));
+        assert_eq!(filter.max_level(), LevelFilter::Info);
+
+        let filter = EnvLogFilter::parse("mycrate=debug");
+        assert_eq!(filter.max_level(), LevelFilter::Debug);
+        assert_eq!(filter.level_for("mycrate::sub"), LevelFilter::Debug);
+        assert_eq!(filter.level_for("mycrate_other"), LevelFilter::Info);
+
+        let filter = EnvLogFilter::parse("WARN, mycrate=error ,mycrate::bs=trace,bad=nope");
+        assert_eq!(filter.max_level(), LevelFilter::Trace);
+        assert_eq!(filter.level_for("mycrate::bs::walk"), LevelFilter::Trace);
+        assert_eq!(filter.level_for("mycrate"), LevelFilter::Error);
+        assert_eq!(filter.level_for("other"), LevelFilter::Warn);
+
+        // invalid bare words are ignored
+        assert_eq!(EnvLogFilter::parse("verbose"), EnvLogFilter::default());
+        assert_eq!(
+            EnvLogFilter::parse("verbose").max_level(),
+            LevelFilter::Info
+        );
+        assert_eq!(
+            EnvLogFilter::parse("verbose,warn").max_level(),
+            LevelFilter::Warn
+        );
+
+        assert_eq!(EnvLogFilter::parse(""), EnvLogFilter::default());
+        assert_eq!(EnvLogFilter::parse("").max_level(), LevelFilter::Info);
+    }
+}
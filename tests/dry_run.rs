@@ -0,0 +1,30 @@
+//! Dry-run mode is global, so this runs in its own test binary
+
+use cli_boilerplate_automation::broc::*;
+use std::process::Command;
+
+#[cfg(unix)]
+#[test]
+fn dry_run_does_not_execute() {
+    let marker = std::env::temp_dir().join(format!("cba_dry_run_{}", std::process::id()));
+    let _ = std::fs::remove_file(&marker);
+    let touch = || {
+        let mut cmd = Command::new("touch");
+        cmd.arg(&marker);
+        cmd
+    };
+
+    set_dry_run(true);
+    assert_eq!(capture_stdout(&mut touch()), None);
+    assert!(capture_output(&mut touch()).is_none());
+    assert!(run_with_stdin(&mut touch(), b"").is_none());
+    assert!(spawn_piped_guarded(&mut touch()).is_err());
+    assert!(spawn_retry(touch, 1, std::time::Duration::ZERO).is_none());
+    assert!(try_spawn_detached(&mut touch()).is_err());
+    set_dry_run(false);
+
+    assert!(!marker.exists());
+    assert_eq!(capture_stdout(&mut touch()).as_deref(), Some(""));
+    assert!(marker.exists());
+    std::fs::remove_file(&marker).unwrap();
+}
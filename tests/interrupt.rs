@@ -0,0 +1,16 @@
+//! The interrupt handler is process-wide and permanent, so this runs in its own test binary
+#![cfg(all(unix, feature = "signal"))]
+
+use cli_boilerplate_automation::bog::Bogger;
+
+#[test]
+fn interrupt_handler_runs() {
+    let (tx, rx) = std::sync::mpsc::channel();
+    assert!(Bogger::on_interrupt(move || {
+        let _ = tx.send(());
+    }));
+    assert!(!Bogger::on_interrupt(|| {}));
+
+    unsafe { libc::raise(libc::SIGINT) };
+    assert!(rx.recv_timeout(std::time::Duration::from_secs(5)).is_ok());
+}